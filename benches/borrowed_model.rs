@@ -0,0 +1,143 @@
+//! Throughput and allocations of the owned and the borrowed notebook model.
+//!
+//! Run with `cargo bench --bench borrowed_model`. Converts a corpus of small
+//! synthetic notebooks, 2000 by default (set `JUPYTER2LLM_BENCH_NOTEBOOKS`),
+//! held in memory, so that only parsing and rendering are measured. A
+//! counting allocator reports the allocations of each model.
+
+use jupyter2llm::borrowed::BorrowedNotebook;
+use jupyter2llm::{JupyterConverter, Notebook};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Number of cells in each notebook.
+const CELLS: usize = 40;
+
+fn main() {
+    let count = std::env::var("JUPYTER2LLM_BENCH_NOTEBOOKS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(2000);
+    let corpus: Vec<String> = (0..count).map(notebook).collect();
+    let size: usize = corpus.iter().map(String::len).sum();
+    println!(
+        "Corpus: {} notebooks, {:.1} MB",
+        count,
+        size as f64 / (1024.0 * 1024.0)
+    );
+
+    let converter = JupyterConverter::new().with_outputs(true);
+    let mut rendered = Vec::new();
+    for model in ["owned", "borrowed"] {
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+        let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed);
+        let start = Instant::now();
+        let mut text = Vec::with_capacity(size);
+        for content in &corpus {
+            text.clear();
+            match model {
+                "owned" => {
+                    let notebook: Notebook = content.parse().expect("parse notebook");
+                    converter.convert_to_writer(&notebook, &mut text)
+                }
+                _ => {
+                    let notebook = BorrowedNotebook::parse(content).expect("parse notebook");
+                    converter.convert_borrowed_to_writer(&notebook, &mut text)
+                }
+            }
+            .expect("convert notebook");
+        }
+        let elapsed = start.elapsed().as_secs_f64();
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+        let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes;
+        rendered.push(text);
+
+        println!(
+            "{:<8} {:>6.2}s  {:>8.0} notebooks/s  {:>6.1} MB/s  {:>6} allocations/notebook  {:>8} bytes allocated/notebook",
+            model,
+            elapsed,
+            count as f64 / elapsed,
+            size as f64 / (1024.0 * 1024.0) / elapsed,
+            allocations / count.max(1),
+            bytes / count.max(1)
+        );
+    }
+    assert_eq!(rendered[0], rendered[1], "models render differently");
+}
+
+/// A notebook with widget metadata, rich outputs and a small image per cell.
+fn notebook(seed: usize) -> String {
+    let cells: Vec<String> = (0..CELLS)
+        .map(|index| {
+            if index % 4 == 0 {
+                format!(
+                    concat!(
+                        "{{\"cell_type\": \"markdown\", \"id\": \"md-{seed}-{index}\", ",
+                        "\"metadata\": {{\"tags\": [\"section\"], \"jp-MarkdownHeadingCollapsed\": true}}, ",
+                        "\"source\": [\"## Step {index}\\n\", \"\\n\", \"Load the data for run {seed}.\"]}}"
+                    ),
+                    seed = seed,
+                    index = index
+                )
+            } else {
+                format!(
+                    concat!(
+                        "{{\"cell_type\": \"code\", \"execution_count\": {index}, \"id\": \"code-{seed}-{index}\", ",
+                        "\"metadata\": {{\"scrolled\": true, \"tags\": [\"train\"], ",
+                        "\"execution\": {{\"iopub.execute_input\": \"2024-01-01T00:00:00.000Z\", ",
+                        "\"shell.execute_reply\": \"2024-01-01T00:00:01.000Z\"}}}}, ",
+                        "\"source\": [\"frame = load(\\\"runs/{seed}.csv\\\")\\n\", \"frame = frame.dropna()\\n\", ",
+                        "\"model.fit(frame, epochs={index})\"], ",
+                        "\"outputs\": [",
+                        "{{\"output_type\": \"stream\", \"name\": \"stdout\", ",
+                        "\"text\": [\"epoch 1: loss=0.{seed}\\n\", \"epoch 2: loss=0.{index}\\n\"]}}, ",
+                        "{{\"output_type\": \"execute_result\", \"execution_count\": {index}, \"metadata\": {{}}, ",
+                        "\"data\": {{\"text/plain\": [\"<Model trained on {seed} rows>\"], ",
+                        "\"text/html\": [\"<div><table><tr><td>{seed}</td></tr></table></div>\"], ",
+                        "\"image/png\": \"{image}\"}}}}]}}"
+                    ),
+                    seed = seed,
+                    index = index,
+                    image = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==".repeat(8)
+                )
+            }
+        })
+        .collect();
+    format!(
+        concat!(
+            "{{\"cells\": [{cells}], \"metadata\": {{",
+            "\"kernelspec\": {{\"display_name\": \"Python 3\", \"language\": \"python\", \"name\": \"python3\"}}, ",
+            "\"language_info\": {{\"name\": \"python\", \"version\": \"3.11.4\"}}, ",
+            "\"widgets\": {{\"application/vnd.jupyter.widget-state+json\": {{\"state\": {{}}, \"version_major\": 2}}}}}}, ",
+            "\"nbformat\": 4, \"nbformat_minor\": 5}}"
+        ),
+        cells = cells.join(", ")
+    )
+}
@@ -0,0 +1,160 @@
+//! Peak memory and throughput of loading and converting a large notebook.
+//!
+//! Run with `cargo bench --bench large_notebook`. The synthetic notebook is
+//! 300 MB by default, mostly base64 images and logs; set
+//! `JUPYTER2LLM_BENCH_MB` to change its size. Each loader runs in a child
+//! process so that its peak resident set size (`VmHWM`, Linux only) is
+//! measured on its own.
+
+use jupyter2llm::{lean, JupyterConverter, Notebook};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::process::Command;
+use std::time::Instant;
+
+const MB: usize = 1024 * 1024;
+/// Size of the base64 image attached to each cell.
+const IMAGE_BYTES: usize = 384 * 1024;
+/// Number of log lines printed by each cell.
+const LOG_LINES: usize = 1000;
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(position) = args.iter().position(|arg| arg == "--child") {
+        run_loader(&args[position + 1], Path::new(&args[position + 2]));
+        return;
+    }
+
+    let megabytes = std::env::var("JUPYTER2LLM_BENCH_MB")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(300);
+    let file = tempfile::Builder::new()
+        .suffix(".ipynb")
+        .tempfile()
+        .expect("create temporary notebook");
+    let cells = write_notebook(file.path(), megabytes * MB).expect("write notebook");
+    let size = std::fs::metadata(file.path()).expect("stat notebook").len() as f64;
+    println!(
+        "Synthetic notebook: {:.0} MB, {} cells",
+        size / MB as f64,
+        cells
+    );
+
+    let executable = std::env::current_exe().expect("locate benchmark executable");
+    for loader in ["full", "lean"] {
+        let output = Command::new(&executable)
+            .arg("--child")
+            .arg(loader)
+            .arg(file.path())
+            .output()
+            .expect("run loader");
+        print!("{}", String::from_utf8_lossy(&output.stdout));
+        if !output.status.success() {
+            eprint!("{}", String::from_utf8_lossy(&output.stderr));
+        }
+    }
+}
+
+/// Loads and converts the notebook with one loader and reports the timings
+/// and the peak memory of this process.
+fn run_loader(loader: &str, path: &Path) {
+    let size = std::fs::metadata(path).expect("stat notebook").len() as f64;
+    let start = Instant::now();
+    let notebook = match loader {
+        "full" => Notebook::from_file(path),
+        _ => lean::from_file(path),
+    }
+    .expect("load notebook");
+    let loaded = start.elapsed().as_secs_f64();
+
+    JupyterConverter::new()
+        .with_outputs(true)
+        .convert_to_writer(&notebook, std::io::sink())
+        .expect("convert notebook");
+    let total = start.elapsed().as_secs_f64();
+
+    println!(
+        "{:<5} load {:>6.2}s  load+convert {:>6.2}s  {:>7.1} MB/s  peak memory {}",
+        loader,
+        loaded,
+        total,
+        size / MB as f64 / total,
+        peak_memory()
+    );
+}
+
+fn peak_memory() -> String {
+    std::fs::read_to_string("/proc/self/status")
+        .ok()
+        .and_then(|status| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix("VmHWM:"))
+                .map(|value| value.trim().to_string())
+        })
+        .unwrap_or_else(|| "n/a".to_string())
+}
+
+/// Writes a notebook of roughly `target` bytes and returns its cell count.
+fn write_notebook(path: &Path, target: usize) -> std::io::Result<usize> {
+    let image = base64_payload(IMAGE_BYTES);
+    let log: Vec<String> = (0..LOG_LINES)
+        .map(|line| {
+            format!(
+                "\"epoch {} step {}: loss=0.{:04}\\n\"",
+                line / 100,
+                line,
+                line
+            )
+        })
+        .collect();
+    let log = log.join(", ");
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(b"{\n \"cells\": [\n")?;
+    let mut written = 0;
+    let mut cells = 0;
+    while written < target {
+        let cell = format!(
+            concat!(
+                "  {{\"cell_type\": \"code\", \"execution_count\": {count}, \"metadata\": {{}},\n",
+                "   \"source\": [\"for step in range(1000):\\n\", \"    train(step)\\n\", \"plot(losses)\"],\n",
+                "   \"outputs\": [\n",
+                "    {{\"output_type\": \"stream\", \"name\": \"stdout\", \"text\": [{log}]}},\n",
+                "    {{\"output_type\": \"display_data\", \"metadata\": {{}}, \"data\": {{\n",
+                "     \"image/png\": \"{image}\",\n",
+                "     \"text/plain\": [\"<Figure size 640x480 with 1 Axes>\"]}}}}\n",
+                "   ]}}"
+            ),
+            count = cells + 1,
+            log = log,
+            image = image,
+        );
+        if cells > 0 {
+            writer.write_all(b",\n")?;
+        }
+        writer.write_all(cell.as_bytes())?;
+        written += cell.len();
+        cells += 1;
+    }
+    writer
+        .write_all(b"\n ],\n \"metadata\": {},\n \"nbformat\": 4,\n \"nbformat_minor\": 4\n}\n")?;
+    writer.flush()?;
+    Ok(cells)
+}
+
+/// Deterministic base64-looking text.
+fn base64_payload(length: usize) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut state: u32 = 0x2545_f491;
+    (0..length)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            ALPHABET[(state % 64) as usize] as char
+        })
+        .collect()
+}
@@ -0,0 +1,377 @@
+//! Path and identity anonymization for outputs and metadata.
+//!
+//! Tracebacks and outputs leak home directories, virtualenv and conda prefixes
+//! and usernames. The anonymizer rewrites them to canonical placeholders such
+//! as `<HOME>` and `<SITE_PACKAGES>` and strips author and identity fields from
+//! notebook metadata. Cell sources are left as written.
+
+use crate::Notebook;
+use regex::{Captures, Regex};
+use serde_json::Value;
+use std::sync::OnceLock;
+
+/// Notebook metadata keys that identify a person.
+const IDENTITY_KEYS: &[&str] = &[
+    "author",
+    "authors",
+    "creator",
+    "email",
+    "last_modified_by",
+    "lastModifiedBy",
+    "owner",
+    "user",
+    "username",
+];
+
+/// Discovered usernames shorter than this are too likely to be ordinary words.
+const MIN_USERNAME_LEN: usize = 3;
+
+/// Characters that can appear in a path segment inside a traceback. ANSI
+/// escapes and quotes end a path.
+const PATH_CHARS: &str = r#"[^\s"'<>`|*?:\x1b]"#;
+
+struct Patterns {
+    site_packages: Regex,
+    environment: Regex,
+    python_lib: Regex,
+    unix_home: Regex,
+    windows_home: Regex,
+    root_home: Regex,
+}
+
+fn patterns() -> &'static Patterns {
+    static PATTERNS: OnceLock<Patterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        let path = |tail: &str| {
+            Regex::new(&format!(
+                r"(?:[A-Za-z]:)?(?:[/\\~]{chars}*?)?[/\\]{tail}",
+                chars = PATH_CHARS,
+                tail = tail
+            ))
+            .unwrap()
+        };
+        Patterns {
+            site_packages: path(r"(?:site|dist)-packages\b"),
+            environment: path(r"(?:(?:envs|virtualenvs)[/\\][\w.-]+|\.?venv|\.tox[/\\][\w.-]+)\b"),
+            python_lib: path(r"lib[/\\]python\d+(?:\.\d+)?\b"),
+            unix_home: Regex::new(r"(?:/home|/Users)/([\w.-]+)").unwrap(),
+            windows_home: Regex::new(r"(?i)[A-Z]:[\\/]+Users[\\/]+([\w.-]+)").unwrap(),
+            root_home: Regex::new(r"(^|[^\w.:/-])/root\b").unwrap(),
+        }
+    })
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Anonymizer {
+    usernames: Vec<String>,
+    hostnames: Vec<String>,
+    /// Usernames found in home directories. Unlike the configured names they
+    /// may be ordinary words such as `data`, so they are only replaced in
+    /// `~user` paths and identity fields.
+    discovered: Vec<String>,
+}
+
+/// The patterns that replace names with placeholders.
+struct Identities {
+    /// Configured hostnames and usernames, replaced wherever they appear.
+    everywhere: Vec<(Regex, &'static str)>,
+    /// Discovered usernames after a `~`.
+    in_paths: Option<Regex>,
+    /// Discovered usernames as words, for the values of identity fields.
+    in_fields: Option<Regex>,
+}
+
+impl Anonymizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces a known username with `<USER>` wherever it appears.
+    pub fn with_username<S: Into<String>>(mut self, username: S) -> Self {
+        self.usernames.push(username.into());
+        self
+    }
+
+    /// Replaces a known hostname with `<HOST>` wherever it appears.
+    pub fn with_hostname<S: Into<String>>(mut self, hostname: S) -> Self {
+        self.hostnames.push(hostname.into());
+        self
+    }
+
+    /// Rewrites outputs and metadata of a notebook in place.
+    pub fn anonymize_notebook(&self, notebook: &mut Notebook) {
+        for key in IDENTITY_KEYS {
+            notebook.metadata.remove(*key);
+        }
+
+        // Usernames seen in home directories anywhere in the notebook are
+        // also replaced in `~user` paths and identity fields elsewhere.
+        let mut anonymizer = self.clone();
+        let mut texts = Vec::new();
+        for cell in &notebook.cells {
+            for output in cell.outputs.iter().flatten() {
+                texts.extend(output.text.iter().flatten().cloned());
+                texts.extend(output.traceback.iter().flatten().cloned());
+                texts.extend(output.evalue.iter().cloned());
+                for value in output.data.iter().flat_map(|data| data.values()) {
+                    collect_strings(value, &mut texts);
+                }
+            }
+        }
+        for value in notebook.metadata.values() {
+            collect_strings(value, &mut texts);
+        }
+        for text in &texts {
+            anonymizer.discover_usernames(text);
+        }
+
+        let identities = anonymizer.identity_patterns();
+
+        for cell in &mut notebook.cells {
+            for (key, value) in cell.metadata.iter_mut() {
+                anonymize_value(value, &identities, is_identity_key(key));
+            }
+            for output in cell.outputs.iter_mut().flatten() {
+                for line in output.text.iter_mut().flatten() {
+                    *line = rewrite(line, &identities);
+                }
+                for line in output.traceback.iter_mut().flatten() {
+                    *line = rewrite(line, &identities);
+                }
+                if let Some(evalue) = &mut output.evalue {
+                    *evalue = rewrite(evalue, &identities);
+                }
+                for value in output.data.iter_mut().flat_map(|data| data.values_mut()) {
+                    anonymize_value(value, &identities, false);
+                }
+            }
+        }
+        for value in notebook.metadata.values_mut() {
+            anonymize_value(value, &identities, false);
+        }
+    }
+
+    fn discover_usernames(&mut self, text: &str) {
+        let patterns = patterns();
+        for captures in patterns
+            .unix_home
+            .captures_iter(text)
+            .chain(patterns.windows_home.captures_iter(text))
+        {
+            let username = &captures[1];
+            if username.len() >= MIN_USERNAME_LEN
+                && !self
+                    .usernames
+                    .iter()
+                    .chain(&self.discovered)
+                    .any(|known| known == username)
+            {
+                self.discovered.push(username.to_string());
+            }
+        }
+    }
+
+    /// Rewrites paths and known identities in a single string.
+    pub fn anonymize_text(&self, text: &str) -> String {
+        rewrite(text, &self.identity_patterns())
+    }
+
+    /// Builds one word-boundary pattern per placeholder from the known names.
+    /// Hostnames go first since they often embed the username.
+    fn identity_patterns(&self) -> Identities {
+        Identities {
+            everywhere: [(&self.hostnames, "<HOST>"), (&self.usernames, "<USER>")]
+                .into_iter()
+                .filter_map(|(names, placeholder)| {
+                    names_pattern(names, r"\b", r"\b").map(|regex| (regex, placeholder))
+                })
+                .collect(),
+            in_paths: names_pattern(&self.discovered, "~", r"\b"),
+            in_fields: names_pattern(&self.discovered, r"\b", r"\b"),
+        }
+    }
+}
+
+/// A pattern matching any of the names between `before` and `after`.
+fn names_pattern(names: &[String], before: &str, after: &str) -> Option<Regex> {
+    let alternatives: Vec<String> = names
+        .iter()
+        .filter(|name| !name.is_empty())
+        .map(|name| regex::escape(name))
+        .collect();
+    if alternatives.is_empty() {
+        return None;
+    }
+    Regex::new(&format!(
+        "{}(?:{}){}",
+        before,
+        alternatives.join("|"),
+        after
+    ))
+    .ok()
+}
+
+fn is_identity_key(key: &str) -> bool {
+    IDENTITY_KEYS.contains(&key)
+}
+
+fn rewrite(text: &str, identities: &Identities) -> String {
+    let patterns = patterns();
+    let text = patterns.site_packages.replace_all(text, "<SITE_PACKAGES>");
+    let text = patterns.environment.replace_all(&text, "<ENV>");
+    let text = patterns.python_lib.replace_all(&text, "<PYTHON_LIB>");
+    let text = patterns.unix_home.replace_all(&text, "<HOME>");
+    let text = patterns.windows_home.replace_all(&text, "<HOME>");
+    let mut text = patterns
+        .root_home
+        .replace_all(&text, |captures: &Captures| {
+            format!("{}<HOME>", &captures[1])
+        })
+        .into_owned();
+
+    for (regex, placeholder) in &identities.everywhere {
+        text = regex.replace_all(&text, *placeholder).into_owned();
+    }
+    if let Some(regex) = &identities.in_paths {
+        text = regex.replace_all(&text, "~<USER>").into_owned();
+    }
+
+    text
+}
+
+/// Rewrites the strings of a JSON value. Discovered usernames are replaced
+/// in the values of identity fields such as `user` or `owner`.
+fn anonymize_value(value: &mut Value, identities: &Identities, identity_field: bool) {
+    match value {
+        Value::String(text) => {
+            *text = rewrite(text, identities);
+            if let Some(regex) = identities.in_fields.as_ref().filter(|_| identity_field) {
+                *text = regex.replace_all(text, "<USER>").into_owned();
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                anonymize_value(item, identities, identity_field);
+            }
+        }
+        Value::Object(map) => {
+            for (key, item) in map.iter_mut() {
+                anonymize_value(item, identities, is_identity_key(key));
+            }
+        }
+        _ => {}
+    }
+}
+
+fn collect_strings(value: &Value, texts: &mut Vec<String>) {
+    match value {
+        Value::String(text) => texts.push(text.clone()),
+        Value::Array(items) => items.iter().for_each(|item| collect_strings(item, texts)),
+        Value::Object(map) => map.values().for_each(|item| collect_strings(item, texts)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_placeholders() {
+        let anonymizer = Anonymizer::new();
+        let cases = [
+            (
+                "File /home/alice/.venv/lib/python3.11/site-packages/pandas/core/frame.py:3761",
+                "File <SITE_PACKAGES>/pandas/core/frame.py:3761",
+            ),
+            (
+                r"C:\Users\bob\miniconda3\envs\ml\python.exe",
+                r"<ENV>\python.exe",
+            ),
+            ("open('/Users/carol/data.csv')", "open('<HOME>/data.csv')"),
+            (
+                "/usr/lib/python3.10/json/decoder.py",
+                "<PYTHON_LIB>/json/decoder.py",
+            ),
+            ("cwd: /root/project", "cwd: <HOME>/project"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(anonymizer.anonymize_text(input), expected);
+        }
+    }
+
+    #[test]
+    fn test_discovered_usernames_stay_in_paths_and_identity_fields() {
+        let mut notebook: Notebook = r#"{
+ "cells": [{"cell_type": "code", "execution_count": 1, "metadata": {"owner": "data"},
+   "source": ["load()"],
+   "outputs": [{"output_type": "stream", "name": "stdout",
+     "text": ["Reading /home/data/sales.csv\n", "ls ~data/raw\n", "Loaded data for 3 regions\n"]}]}],
+ "metadata": {"jupyter": {"user": "data"}}, "nbformat": 4, "nbformat_minor": 4
+}"#
+        .parse()
+        .unwrap();
+        Anonymizer::new().anonymize_notebook(&mut notebook);
+
+        let cell = &notebook.cells[0];
+        assert_eq!(
+            cell.outputs.as_ref().unwrap()[0].text.as_ref().unwrap(),
+            &[
+                "Reading <HOME>/sales.csv\n",
+                "ls ~<USER>/raw\n",
+                "Loaded data for 3 regions\n"
+            ]
+        );
+        assert_eq!(cell.metadata["owner"], "<USER>");
+        assert_eq!(notebook.metadata["jupyter"]["user"], "<USER>");
+    }
+
+    #[test]
+    fn test_known_identities() {
+        let anonymizer = Anonymizer::new()
+            .with_username("alice")
+            .with_hostname("gpu-box-7");
+        assert_eq!(
+            anonymizer.anonymize_text("alice@gpu-box-7:~$ whoami\nalice"),
+            "<USER>@<HOST>:~$ whoami\n<USER>"
+        );
+    }
+
+    #[test]
+    fn test_tracebacks_and_metadata() {
+        let mut notebook: Notebook = r#"{
+ "cells": [{"cell_type": "code", "execution_count": 3, "metadata": {}, "source": ["df['region']"],
+   "outputs": [
+    {"output_type": "stream", "name": "stdout",
+     "text": ["Loading /home/alice/projects/sales/data.csv on alice-laptop\n"]},
+    {"output_type": "error", "ename": "KeyError", "evalue": "'region'",
+     "traceback": [
+      "File \u001b[0;32m/home/alice/.venv/lib/python3.11/site-packages/pandas/core/frame.py:3761\u001b[0m, in \u001b[0;36mDataFrame.__getitem__\u001b[0m\n",
+      "\u001b[0;31mKeyError\u001b[0m: 'region'"]}]}],
+ "metadata": {
+  "authors": [{"name": "Alice Example"}],
+  "vscode": {"interpreter": {"path": "/home/alice/.venv/bin/python"}}
+ },
+ "nbformat": 4, "nbformat_minor": 5
+}"#
+        .parse()
+        .unwrap();
+        Anonymizer::new()
+            .with_hostname("alice-laptop")
+            .anonymize_notebook(&mut notebook);
+
+        let outputs = notebook.cells[0].outputs.as_ref().unwrap();
+        assert_eq!(
+            outputs[0].text.as_ref().unwrap()[0],
+            "Loading <HOME>/projects/sales/data.csv on <HOST>\n"
+        );
+        let traceback = outputs[1].traceback.as_ref().unwrap().join("\n");
+        assert!(traceback.contains("<SITE_PACKAGES>/pandas/core/frame.py:3761"));
+        assert!(!traceback.contains("alice"));
+        assert!(!notebook.metadata.contains_key("authors"));
+        assert_eq!(
+            notebook.metadata["vscode"]["interpreter"]["path"],
+            "<ENV>/bin/python"
+        );
+    }
+}
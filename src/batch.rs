@@ -0,0 +1,560 @@
+//! Conversion of many notebooks in one run.
+//!
+//! Inputs are notebook files, directories, which are searched recursively,
+//! and glob patterns such as `notebooks/**/*.ipynb`. Hidden directories,
+//! including `.ipynb_checkpoints`, are skipped. Each notebook is converted
+//! into a mirrored file under an output directory or appended to one
+//! concatenated output. A notebook that fails is recorded in the
+//! `BatchReport` and does not stop the batch. `BatchRunner` converts
+//! notebooks on several threads and keeps the output in input order.
+
+use crate::diagnostic::{Conversion, Diagnostic, Severity};
+use crate::{JupyterConverter, JupyterError};
+use regex::Regex;
+use std::collections::HashSet;
+use std::fmt;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+/// File name of a converted notebook under the output directory.
+pub const DEFAULT_NAME_PATTERN: &str = "{stem}.txt";
+
+/// A notebook found for one of the inputs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchInput {
+    pub path: PathBuf,
+    /// Path below the directory or the literal part of the glob pattern it
+    /// was found in, mirrored under the output directory. The file name for
+    /// a notebook given directly.
+    pub relative: PathBuf,
+}
+
+/// Where the converted notebooks go.
+pub enum BatchOutput<'a> {
+    /// One file per notebook under `dir`, at the notebook's relative path,
+    /// named by `pattern` (see `output_path`).
+    Directory { dir: PathBuf, pattern: String },
+    /// All notebooks in input order, each under a `# File:` heading.
+    Concatenate(&'a mut dyn Write),
+}
+
+#[derive(Debug)]
+pub struct FileReport {
+    pub path: PathBuf,
+    /// The file written in directory mode.
+    pub output: Option<PathBuf>,
+    pub result: Result<Vec<Diagnostic>, JupyterError>,
+}
+
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    pub files: Vec<FileReport>,
+}
+
+/// Expands files, directories and glob patterns into the notebooks to
+/// convert, in input order and without duplicates. Notebooks within a
+/// directory or pattern are sorted by path.
+pub fn collect_inputs<S: AsRef<str>>(inputs: &[S]) -> Result<Vec<BatchInput>, JupyterError> {
+    let mut seen = HashSet::new();
+    let mut collected = Vec::new();
+
+    for input in inputs {
+        let input = input.as_ref();
+        let path = Path::new(input);
+        let found = if path.is_file() {
+            let relative = path.file_name().map(PathBuf::from).unwrap_or_default();
+            vec![BatchInput {
+                path: path.to_path_buf(),
+                relative,
+            }]
+        } else if path.is_dir() {
+            find_notebooks(path, &|_| true)?
+        } else if is_glob(input) {
+            let (base, pattern) = split_glob(input);
+            let matcher = glob_regex(&pattern)?;
+            find_notebooks(&base, &|relative| {
+                matcher.is_match(&relative.to_string_lossy().replace('\\', "/"))
+            })?
+        } else {
+            return Err(JupyterError::InputNotFound(input.to_string()));
+        };
+
+        if found.is_empty() {
+            return Err(JupyterError::InputNotFound(input.to_string()));
+        }
+        for input in found {
+            if seen.insert(input.path.clone()) {
+                collected.push(input);
+            }
+        }
+    }
+
+    Ok(collected)
+}
+
+/// Path of the converted notebook under `dir`. The pattern may use
+/// `{stem}`, the notebook file name without extension, and `{name}`, the
+/// full file name.
+pub fn output_path(dir: &Path, input: &BatchInput, pattern: &str) -> PathBuf {
+    let name = input
+        .relative
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let stem = input
+        .relative
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let file_name = pattern.replace("{stem}", &stem).replace("{name}", &name);
+
+    let mut path = dir.to_path_buf();
+    if let Some(parent) = input.relative.parent() {
+        path.push(parent);
+    }
+    path.push(file_name);
+    path
+}
+
+/// Aggregated progress of a running batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub done: usize,
+    pub failed: usize,
+    pub total: usize,
+}
+
+/// Converts a batch of notebooks on one or more threads.
+///
+/// Notebooks finish in any order, but the concatenated output and the report
+/// always follow input order, so the result does not depend on the number of
+/// jobs. Only a failure to write the concatenated output ends the batch
+/// early; everything else is recorded per file.
+pub struct BatchRunner<'a> {
+    converter: &'a JupyterConverter,
+    jobs: usize,
+    progress: Option<Box<dyn FnMut(Progress) + 'a>>,
+}
+
+impl<'a> BatchRunner<'a> {
+    pub fn new(converter: &'a JupyterConverter) -> Self {
+        Self {
+            converter,
+            jobs: 1,
+            progress: None,
+        }
+    }
+
+    /// Number of notebooks converted at the same time (default 1).
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+
+    /// Calls `progress` after each notebook, in the order they finish.
+    pub fn with_progress(mut self, progress: impl FnMut(Progress) + 'a) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    pub fn run(
+        mut self,
+        inputs: &[BatchInput],
+        output: BatchOutput,
+    ) -> Result<BatchReport, JupyterError> {
+        // Output files are assigned in input order, so a conflict always
+        // fails the later notebook.
+        let (targets, writer) = match output {
+            BatchOutput::Directory { dir, pattern } => {
+                let mut claimed = HashSet::new();
+                let targets = inputs
+                    .iter()
+                    .map(|input| {
+                        let target = output_path(&dir, input, &pattern);
+                        if claimed.insert(target.clone()) {
+                            Target::File(target)
+                        } else {
+                            Target::Conflict(target)
+                        }
+                    })
+                    .collect();
+                (targets, None)
+            }
+            BatchOutput::Concatenate(writer) => {
+                (vec![Target::Concatenate; inputs.len()], Some(writer))
+            }
+        };
+
+        let mut collector = Collector {
+            inputs,
+            targets: &targets,
+            writer,
+            pending: (0..inputs.len()).map(|_| None).collect(),
+            report: BatchReport::default(),
+            progress: Progress {
+                done: 0,
+                failed: 0,
+                total: inputs.len(),
+            },
+            on_progress: self.progress.take(),
+        };
+        let converter = self.converter;
+
+        if self.jobs == 1 || inputs.len() < 2 {
+            for (index, input) in inputs.iter().enumerate() {
+                collector.finish(index, convert(converter, input, &targets[index]))?;
+            }
+        } else {
+            let next = AtomicUsize::new(0);
+            thread::scope(|scope| {
+                let (sender, receiver) = mpsc::channel();
+                for _ in 0..self.jobs.min(inputs.len()) {
+                    let sender = sender.clone();
+                    let (next, targets) = (&next, &targets);
+                    scope.spawn(move || loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(input) = inputs.get(index) else {
+                            break;
+                        };
+                        let result = convert(converter, input, &targets[index]);
+                        // The receiver is gone once writing the output failed.
+                        if sender.send((index, result)).is_err() {
+                            break;
+                        }
+                    });
+                }
+                drop(sender);
+                for (index, result) in receiver {
+                    collector.finish(index, result)?;
+                }
+                Ok::<_, JupyterError>(())
+            })?;
+        }
+
+        Ok(collector.report)
+    }
+}
+
+/// Converts every input one after another; see `BatchRunner` for parallel
+/// conversion and progress.
+pub fn convert_batch(
+    converter: &JupyterConverter,
+    inputs: &[BatchInput],
+    output: BatchOutput,
+) -> Result<BatchReport, JupyterError> {
+    BatchRunner::new(converter).run(inputs, output)
+}
+
+#[derive(Clone)]
+enum Target {
+    Concatenate,
+    File(PathBuf),
+    /// The file is already written by an earlier notebook.
+    Conflict(PathBuf),
+}
+
+/// Converts one notebook on a worker thread. In directory mode the file is
+/// written here and the returned text is empty.
+fn convert(
+    converter: &JupyterConverter,
+    input: &BatchInput,
+    target: &Target,
+) -> Result<Conversion, JupyterError> {
+    match target {
+        Target::Concatenate => converter.convert_file(&input.path),
+        Target::File(path) => {
+            let conversion = converter.convert_file(&input.path)?;
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent).map_err(JupyterError::WriteError)?;
+            }
+            std::fs::write(path, &conversion.text).map_err(JupyterError::WriteError)?;
+            Ok(Conversion {
+                text: String::new(),
+                diagnostics: conversion.diagnostics,
+            })
+        }
+        Target::Conflict(path) => Err(JupyterError::OutputConflict(path.display().to_string())),
+    }
+}
+
+/// Puts finished notebooks back into input order.
+struct Collector<'a, 'w, 'p> {
+    inputs: &'a [BatchInput],
+    targets: &'a [Target],
+    writer: Option<&'w mut dyn Write>,
+    /// Finished notebooks that wait for an earlier one.
+    pending: Vec<Option<Result<Conversion, JupyterError>>>,
+    report: BatchReport,
+    progress: Progress,
+    on_progress: Option<Box<dyn FnMut(Progress) + 'p>>,
+}
+
+impl Collector<'_, '_, '_> {
+    fn finish(
+        &mut self,
+        index: usize,
+        result: Result<Conversion, JupyterError>,
+    ) -> Result<(), JupyterError> {
+        self.progress.done += 1;
+        if result.is_err() {
+            self.progress.failed += 1;
+        }
+        if let Some(on_progress) = self.on_progress.as_mut() {
+            on_progress(self.progress);
+        }
+
+        self.pending[index] = Some(result);
+        while let Some(result) = self
+            .pending
+            .get_mut(self.report.files.len())
+            .and_then(Option::take)
+        {
+            let position = self.report.files.len();
+            let input = &self.inputs[position];
+            if let (Some(writer), Ok(conversion)) = (self.writer.as_mut(), &result) {
+                write!(
+                    writer,
+                    "# File: {}\n\n{}",
+                    input.path.display(),
+                    conversion.text
+                )
+                .map_err(JupyterError::WriteError)?;
+            }
+            let output = match &self.targets[position] {
+                Target::File(path) if result.is_ok() => Some(path.clone()),
+                _ => None,
+            };
+            self.report.files.push(FileReport {
+                path: input.path.clone(),
+                output,
+                result: result.map(|conversion| conversion.diagnostics),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl BatchReport {
+    pub fn succeeded(&self) -> usize {
+        self.files.iter().filter(|file| file.result.is_ok()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.files.len() - self.succeeded()
+    }
+}
+
+impl fmt::Display for BatchReport {
+    /// A summary table with one row per notebook.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<8} {:>8} {:>6}  File", "Status", "Warnings", "Notes")?;
+        for file in &self.files {
+            match &file.result {
+                Ok(diagnostics) => {
+                    let count = |severity| {
+                        diagnostics
+                            .iter()
+                            .filter(|diagnostic| diagnostic.severity == severity)
+                            .count()
+                    };
+                    write!(
+                        f,
+                        "{:<8} {:>8} {:>6}  {}",
+                        "ok",
+                        count(Severity::Warning),
+                        count(Severity::Note),
+                        file.path.display()
+                    )?;
+                    if let Some(output) = &file.output {
+                        write!(f, " -> {}", output.display())?;
+                    }
+                    writeln!(f)?;
+                }
+                Err(err) => writeln!(
+                    f,
+                    "{:<8} {:>8} {:>6}  {}: {}",
+                    "FAILED",
+                    "-",
+                    "-",
+                    file.path.display(),
+                    err
+                )?,
+            }
+        }
+        write!(
+            f,
+            "{} notebook(s): {} converted, {} failed",
+            self.files.len(),
+            self.succeeded(),
+            self.failed()
+        )
+    }
+}
+
+/// Recursively finds `.ipynb` files below `base` whose path relative to
+/// `base` is accepted, skipping hidden directories. Symlinked directories are
+/// not followed, so a link to an ancestor cannot loop.
+fn find_notebooks(
+    base: &Path,
+    accept: &dyn Fn(&Path) -> bool,
+) -> Result<Vec<BatchInput>, JupyterError> {
+    let mut found = Vec::new();
+    let mut pending = vec![base.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let hidden = path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+            if entry.file_type()?.is_dir() {
+                if !hidden {
+                    pending.push(path);
+                }
+                continue;
+            }
+            if path.extension().is_none_or(|ext| ext != "ipynb") {
+                continue;
+            }
+            let relative = path.strip_prefix(base).unwrap_or(&path).to_path_buf();
+            if accept(&relative) {
+                found.push(BatchInput { path, relative });
+            }
+        }
+    }
+
+    found.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(found)
+}
+
+fn is_glob(input: &str) -> bool {
+    input.contains(['*', '?', '['])
+}
+
+/// Splits a glob into the directory of its leading literal components and
+/// the pattern for the rest.
+fn split_glob(input: &str) -> (PathBuf, String) {
+    let path = Path::new(input);
+    let mut base = PathBuf::new();
+    let mut rest = Vec::new();
+    for component in path.components() {
+        let text = component.as_os_str().to_string_lossy();
+        if rest.is_empty() && !is_glob(&text) && component != Component::CurDir {
+            base.push(component);
+        } else if component != Component::CurDir {
+            rest.push(text.into_owned());
+        }
+    }
+    if base.as_os_str().is_empty() {
+        base.push(".");
+    }
+    (base, rest.join("/"))
+}
+
+/// Translates a glob into an anchored regex over `/`-separated relative
+/// paths. `*` and `?` stay within one component; `**/` matches any number
+/// of directories.
+fn glob_regex(pattern: &str) -> Result<Regex, JupyterError> {
+    let mut regex = String::from("^");
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:[^/]*/)*");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => {
+                let mut class = String::new();
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        break;
+                    }
+                    class.push(c);
+                }
+                let class = match class.strip_prefix('!') {
+                    Some(negated) => format!("^{}", negated),
+                    None => class,
+                };
+                regex.push_str(&format!("[{}]", class.replace('\\', "\\\\")));
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex).map_err(|err| JupyterError::InvalidPattern(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_regex() {
+        let regex = glob_regex("**/*.ipynb").unwrap();
+        assert!(regex.is_match("a.ipynb"));
+        assert!(regex.is_match("runs/2024/a.ipynb"));
+        assert!(!regex.is_match("a.ipynb.bak"));
+
+        let regex = glob_regex("exp-[0-9]?/*.ipynb").unwrap();
+        assert!(regex.is_match("exp-12/train.ipynb"));
+        assert!(!regex.is_match("exp-12/sub/train.ipynb"));
+        assert!(!regex.is_match("exp-a1/train.ipynb"));
+
+        assert_eq!(
+            split_glob("notebooks/*/run?.ipynb"),
+            (PathBuf::from("notebooks"), "*/run?.ipynb".to_string())
+        );
+        assert_eq!(
+            split_glob("*.ipynb"),
+            (PathBuf::from("."), "*.ipynb".to_string())
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinked_directories_are_not_followed() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("runs");
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::write(dir.join("train.ipynb"), "{}").unwrap();
+        std::os::unix::fs::symlink(root.path(), dir.join("parent")).unwrap();
+
+        let found = find_notebooks(root.path(), &|_| true).unwrap();
+        let relative: Vec<&Path> = found.iter().map(|input| input.relative.as_path()).collect();
+        assert_eq!(relative, vec![Path::new("runs/train.ipynb")]);
+    }
+
+    #[test]
+    fn test_converter_is_shareable_across_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<JupyterConverter>();
+        assert_send_sync::<JupyterError>();
+    }
+
+    #[test]
+    fn test_output_path() {
+        let input = BatchInput {
+            path: PathBuf::from("repo/runs/train.ipynb"),
+            relative: PathBuf::from("runs/train.ipynb"),
+        };
+        assert_eq!(
+            output_path(Path::new("out"), &input, DEFAULT_NAME_PATTERN),
+            PathBuf::from("out/runs/train.txt")
+        );
+        assert_eq!(
+            output_path(Path::new("out"), &input, "{name}.md"),
+            PathBuf::from("out/runs/train.ipynb.md")
+        );
+    }
+}
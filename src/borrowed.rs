@@ -0,0 +1,368 @@
+//! A notebook model that borrows from the JSON text.
+//!
+//! Converting a corpus of many small notebooks is dominated by allocation:
+//! the owned model copies every source line into a `String` and builds a map
+//! of `serde_json::Value`s for every metadata object and MIME bundle.
+//! `BorrowedNotebook` borrows strings from the input where possible, as a
+//! `Cow` that is only owned when the JSON string contains escapes, and keeps
+//! metadata and MIME payloads as unparsed `RawValue`s. They are parsed only
+//! when the renderer reads them, which for MIME data means `text/plain` and
+//! tracebacks.
+//!
+//! Source lines that end in an escaped `\n` still allocate, since the escape
+//! has to be decoded; the last line of each cell and most MIME types do not.
+
+use crate::view::{self, CellView, OutputView};
+use crate::{location, Cell, JupyterError, Notebook, Output};
+use serde::de::{self, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use serde_json::value::RawValue;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// A MIME bundle as `(MIME type, unparsed payload)` pairs, sorted by MIME type.
+pub type MimeBundle<'a> = Vec<(Cow<'a, str>, &'a RawValue)>;
+
+#[derive(Debug, Deserialize)]
+pub struct BorrowedNotebook<'a> {
+    #[serde(borrow)]
+    pub cells: Vec<BorrowedCell<'a>>,
+    pub metadata: &'a RawValue,
+    pub nbformat: u32,
+    pub nbformat_minor: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BorrowedCell<'a> {
+    #[serde(borrow)]
+    pub cell_type: Cow<'a, str>,
+    #[serde(borrow, deserialize_with = "multiline")]
+    pub source: Vec<Cow<'a, str>>,
+    pub metadata: &'a RawValue,
+    #[serde(borrow)]
+    pub outputs: Option<Vec<BorrowedOutput<'a>>>,
+    pub execution_count: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BorrowedOutput<'a> {
+    #[serde(borrow)]
+    pub output_type: Cow<'a, str>,
+    #[serde(default, borrow, deserialize_with = "optional_multiline")]
+    pub text: Option<Vec<Cow<'a, str>>>,
+    #[serde(default, borrow, deserialize_with = "mime_bundle")]
+    pub data: Option<MimeBundle<'a>>,
+    pub execution_count: Option<u32>,
+    #[serde(borrow)]
+    pub ename: Option<Cow<'a, str>>,
+    #[serde(borrow)]
+    pub evalue: Option<Cow<'a, str>>,
+    #[serde(default, borrow, deserialize_with = "optional_lines")]
+    pub traceback: Option<Vec<Cow<'a, str>>>,
+}
+
+impl<'a> BorrowedNotebook<'a> {
+    /// Parses a notebook without copying the strings it can borrow.
+    ///
+    /// Accepts the same documents as `Notebook::from_str` and fails with the
+    /// same, located errors.
+    pub fn parse(content: &'a str) -> Result<Self, JupyterError> {
+        let parsed = serde_json::from_str::<Self>(content);
+        match parsed {
+            Ok(notebook) if notebook.metadata_are_objects() => Ok(notebook),
+            Ok(_) => Err(content.parse::<Notebook>().err().unwrap_or_else(|| {
+                JupyterError::InvalidNotebookFormat {
+                    message: "metadata must be an object".to_string(),
+                    location: None,
+                }
+            })),
+            // The owned parser locates the error; it also fails on anything
+            // this parser rejects.
+            Err(err) => match content.parse::<Notebook>() {
+                Err(located) => Err(located),
+                Ok(_) => Err(location::parse_error(err, content)),
+            },
+        }
+    }
+
+    /// Copies the notebook into the owned model.
+    pub fn to_notebook(&self) -> Result<Notebook, JupyterError> {
+        Ok(Notebook {
+            cells: self
+                .cells
+                .iter()
+                .map(BorrowedCell::to_cell)
+                .collect::<Result<_, _>>()?,
+            metadata: parse_raw(self.metadata)?,
+            nbformat: self.nbformat,
+            nbformat_minor: self.nbformat_minor,
+        })
+    }
+
+    /// The owned model requires metadata objects; `RawValue` accepts any
+    /// JSON value.
+    fn metadata_are_objects(&self) -> bool {
+        std::iter::once(self.metadata)
+            .chain(self.cells.iter().map(|cell| cell.metadata))
+            .all(|metadata| metadata.get().starts_with('{'))
+    }
+}
+
+impl BorrowedCell<'_> {
+    pub fn to_cell(&self) -> Result<Cell, JupyterError> {
+        Ok(Cell {
+            cell_type: self.cell_type.to_string(),
+            source: to_strings(&self.source),
+            metadata: parse_raw(self.metadata)?,
+            outputs: self
+                .outputs
+                .as_ref()
+                .map(|outputs| outputs.iter().map(BorrowedOutput::to_output).collect())
+                .transpose()?,
+            execution_count: self.execution_count,
+        })
+    }
+}
+
+impl BorrowedOutput<'_> {
+    pub fn to_output(&self) -> Result<Output, JupyterError> {
+        Ok(Output {
+            output_type: self.output_type.to_string(),
+            text: self.text.as_deref().map(to_strings),
+            data: self
+                .data
+                .as_ref()
+                .map(|bundle| {
+                    bundle
+                        .iter()
+                        .map(|(mime, raw)| Ok((mime.to_string(), serde_json::from_str(raw.get())?)))
+                        .collect::<Result<_, JupyterError>>()
+                })
+                .transpose()?,
+            execution_count: self.execution_count,
+            ename: self.ename.as_deref().map(str::to_string),
+            evalue: self.evalue.as_deref().map(str::to_string),
+            traceback: self.traceback.as_deref().map(to_strings),
+        })
+    }
+
+    fn data(&self, mime_type: &str) -> Option<&RawValue> {
+        let bundle = self.data.as_ref()?;
+        bundle
+            .binary_search_by(|(mime, _)| mime.as_ref().cmp(mime_type))
+            .ok()
+            .map(|position| bundle[position].1)
+    }
+}
+
+impl<'a> CellView for BorrowedCell<'a> {
+    type Output = BorrowedOutput<'a>;
+
+    fn cell_type(&self) -> &str {
+        &self.cell_type
+    }
+
+    fn source(&self) -> impl Iterator<Item = &str> {
+        self.source.iter().map(|line| line.as_ref())
+    }
+
+    fn execution_count(&self) -> Option<u32> {
+        self.execution_count
+    }
+
+    fn outputs(&self) -> &[BorrowedOutput<'a>] {
+        self.outputs.as_deref().unwrap_or_default()
+    }
+}
+
+impl OutputView for BorrowedOutput<'_> {
+    fn output_type(&self) -> &str {
+        &self.output_type
+    }
+
+    fn text(&self) -> Option<impl Iterator<Item = &str>> {
+        Some(self.text.as_ref()?.iter().map(|line| line.as_ref()))
+    }
+
+    fn mime_types(&self) -> Option<Vec<&str>> {
+        Some(
+            self.data
+                .as_ref()?
+                .iter()
+                .map(|(mime, _)| mime.as_ref())
+                .collect(),
+        )
+    }
+
+    fn plain_text(&self) -> Option<Vec<Cow<'_, str>>> {
+        let value: Value = serde_json::from_str(self.data("text/plain")?.get()).ok()?;
+        let lines = view::plain_text(&value)?;
+        Some(
+            lines
+                .into_iter()
+                .map(|line| Cow::Owned(line.into_owned()))
+                .collect(),
+        )
+    }
+
+    fn traceback_lines(&self) -> Option<Vec<Cow<'_, str>>> {
+        if let Some(traceback) = &self.traceback {
+            return Some(
+                traceback
+                    .iter()
+                    .map(|line| Cow::Borrowed(line.as_ref()))
+                    .collect(),
+            );
+        }
+        let value: Value = serde_json::from_str(self.data("traceback")?.get()).ok()?;
+        Some(
+            value
+                .as_array()
+                .map(|items| {
+                    items
+                        .iter()
+                        .filter_map(|item| item.as_str().map(|line| Cow::Owned(line.to_string())))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        )
+    }
+
+    fn has_nested_traceback(&self) -> bool {
+        self.traceback.is_none() && self.data("traceback").is_some()
+    }
+
+    fn as_output(&self) -> Cow<'_, Output> {
+        // Every payload parsed as JSON when the notebook was read.
+        Cow::Owned(self.to_output().expect("raw values are valid JSON"))
+    }
+}
+
+/// A string that is borrowed unless it contains escapes. serde only borrows
+/// a `Cow` that is a field of its own, not one inside a `Vec`.
+#[derive(Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+struct Line<'a>(#[serde(borrow)] Cow<'a, str>);
+
+/// A multiline string as borrowed lines. A single string is split into lines
+/// like in the owned model.
+struct Multiline<'a>(Vec<Cow<'a, str>>);
+
+impl<'de> Deserialize<'de> for Multiline<'de> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct MultilineVisitor;
+
+        impl<'de> Visitor<'de> for MultilineVisitor {
+            type Value = Multiline<'de>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a string or an array of strings")
+            }
+
+            fn visit_borrowed_str<E: de::Error>(self, text: &'de str) -> Result<Self::Value, E> {
+                Ok(Multiline(
+                    text.split_inclusive('\n').map(Cow::Borrowed).collect(),
+                ))
+            }
+
+            fn visit_str<E: de::Error>(self, text: &str) -> Result<Self::Value, E> {
+                Ok(Multiline(
+                    text.split_inclusive('\n')
+                        .map(|line| Cow::Owned(line.to_string()))
+                        .collect(),
+                ))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut lines = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(line) = seq.next_element::<Line<'de>>()? {
+                    lines.push(line.0);
+                }
+                Ok(Multiline(lines))
+            }
+        }
+
+        deserializer.deserialize_any(MultilineVisitor)
+    }
+}
+
+fn multiline<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Cow<'de, str>>, D::Error> {
+    Ok(Multiline::deserialize(deserializer)?.0)
+}
+
+fn optional_multiline<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<Cow<'de, str>>>, D::Error> {
+    Ok(Option::<Multiline<'de>>::deserialize(deserializer)?.map(|lines| lines.0))
+}
+
+fn optional_lines<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<Cow<'de, str>>>, D::Error> {
+    let lines = Option::<Vec<Line<'de>>>::deserialize(deserializer)?;
+    Ok(lines.map(|lines| lines.into_iter().map(|line| line.0).collect()))
+}
+
+fn mime_bundle<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<MimeBundle<'de>>, D::Error> {
+    let bundle = Option::<BTreeMap<Line<'de>, &'de RawValue>>::deserialize(deserializer)?;
+    Ok(bundle.map(|bundle| {
+        bundle
+            .into_iter()
+            .map(|(mime, raw)| (mime.0, raw))
+            .collect()
+    }))
+}
+
+fn to_strings(lines: &[Cow<'_, str>]) -> Vec<String> {
+    lines.iter().map(|line| line.to_string()).collect()
+}
+
+fn parse_raw(raw: &RawValue) -> Result<HashMap<String, Value>, JupyterError> {
+    Ok(serde_json::from_str(raw.get())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTEBOOK: &str = r#"{
+ "cells": [{"cell_type": "code", "execution_count": 1, "metadata": {"tags": ["setup"]},
+   "source": ["import os\n", "print(os.name)"],
+   "outputs": [{"output_type": "execute_result", "execution_count": 1, "metadata": {},
+     "data": {"text/plain": ["'posix'"], "image/png": "iVBORw0KGgo="}}]}],
+ "metadata": {"kernelspec": {"name": "python3"}}, "nbformat": 4, "nbformat_minor": 4
+}"#;
+
+    #[test]
+    fn test_strings_are_borrowed_unless_escaped() {
+        let notebook = BorrowedNotebook::parse(NOTEBOOK).unwrap();
+        let cell = &notebook.cells[0];
+        assert!(matches!(cell.cell_type, Cow::Borrowed("code")));
+        assert!(matches!(cell.source[0], Cow::Owned(_)));
+        assert!(matches!(cell.source[1], Cow::Borrowed("print(os.name)")));
+        assert_eq!(cell.metadata.get(), r#"{"tags": ["setup"]}"#);
+
+        let output = &cell.outputs()[0];
+        assert_eq!(output.mime_types(), Some(vec!["image/png", "text/plain"]));
+        assert_eq!(output.plain_text(), Some(vec![Cow::Borrowed("'posix'")]));
+    }
+
+    #[test]
+    fn test_to_notebook_matches_owned_parse() {
+        let borrowed = BorrowedNotebook::parse(NOTEBOOK)
+            .unwrap()
+            .to_notebook()
+            .unwrap();
+        let owned: Notebook = NOTEBOOK.parse().unwrap();
+        assert_eq!(
+            serde_json::to_value(&borrowed).unwrap(),
+            serde_json::to_value(&owned).unwrap()
+        );
+
+        let invalid = NOTEBOOK.replace(r#""metadata": {"tags": ["setup"]}"#, r#""metadata": 1"#);
+        assert!(BorrowedNotebook::parse(&invalid).is_err());
+    }
+}
@@ -0,0 +1,282 @@
+//! Cross-cell dependency graph built from the names code cells bind and read.
+//!
+//! A cell depends on the cell that last bound a name before it. When no
+//! earlier cell binds the name but a later one does, the cell depends on that
+//! later cell and the edge is marked as forward: the notebook only worked
+//! because the cells were run out of document order.
+
+use crate::outline;
+use crate::python::{self, CellNames};
+use crate::Notebook;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dependency {
+    /// Index of the cell that reads the names.
+    pub cell: usize,
+    /// Index of the cell that binds them.
+    pub source: usize,
+    pub names: Vec<String>,
+    /// True when the binding cell comes later in the document.
+    pub forward: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DependencyGraph {
+    /// Names of each code cell, by cell index.
+    pub cells: BTreeMap<usize, CellNames>,
+    /// Edges ordered by reading cell, then binding cell.
+    pub edges: Vec<Dependency>,
+    summaries: BTreeMap<usize, String>,
+}
+
+impl DependencyGraph {
+    pub fn build(notebook: &Notebook) -> Self {
+        let mut graph = Self::default();
+        for (index, cell) in notebook.cells.iter().enumerate() {
+            if cell.cell_type == "code" {
+                graph.cells.insert(index, python::analyze_cell(cell));
+                graph
+                    .summaries
+                    .insert(index, outline::summarize_code_cell(cell));
+            }
+        }
+
+        for (&index, names) in &graph.cells {
+            let mut sources: BTreeMap<(usize, bool), Vec<String>> = BTreeMap::new();
+            for name in &names.referenced {
+                let earlier = graph
+                    .cells
+                    .range(..index)
+                    .rev()
+                    .find(|(_, other)| other.binds(name));
+                let source = match earlier {
+                    Some((&source, _)) => Some((source, false)),
+                    None => graph
+                        .cells
+                        .range(index + 1..)
+                        .find(|(_, other)| other.binds(name))
+                        .map(|(&source, _)| (source, true)),
+                };
+                if let Some(source) = source {
+                    sources.entry(source).or_default().push(name.clone());
+                }
+            }
+
+            graph.edges.extend(
+                sources
+                    .into_iter()
+                    .map(|((source, forward), names)| Dependency {
+                        cell: index,
+                        source,
+                        names,
+                        forward,
+                    }),
+            );
+        }
+
+        graph
+    }
+
+    /// Edges from cells that `cell` reads names from.
+    pub fn dependencies(&self, cell: usize) -> impl Iterator<Item = &Dependency> {
+        self.edges.iter().filter(move |edge| edge.cell == cell)
+    }
+
+    /// Edges to cells that read names bound by `cell`.
+    pub fn dependents(&self, cell: usize) -> impl Iterator<Item = &Dependency> {
+        self.edges.iter().filter(move |edge| edge.source == cell)
+    }
+
+    /// Returns `cell` and every cell it transitively depends on.
+    pub fn closure(&self, cell: usize) -> BTreeSet<usize> {
+        let mut closure = BTreeSet::from([cell]);
+        let mut pending = vec![cell];
+        while let Some(current) = pending.pop() {
+            for edge in self.dependencies(current) {
+                if closure.insert(edge.source) {
+                    pending.push(edge.source);
+                }
+            }
+        }
+        closure
+    }
+
+    /// Renders the graph in Graphviz DOT format. Edges point from the cell
+    /// that binds a name to the cell that reads it; forward edges are dashed.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph dependencies {\n    node [shape=box];\n");
+        for (index, summary) in &self.summaries {
+            dot.push_str(&format!(
+                "    cell{} [label=\"Cell {}: {}\"];\n",
+                index + 1,
+                index + 1,
+                escape_dot(summary)
+            ));
+        }
+        for edge in &self.edges {
+            dot.push_str(&format!(
+                "    cell{} -> cell{} [label=\"{}\"{}];\n",
+                edge.source + 1,
+                edge.cell + 1,
+                escape_dot(&edge.names.join(", ")),
+                if edge.forward { ", style=dashed" } else { "" }
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl fmt::Display for DependencyGraph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows: Vec<(String, String, String)> = self
+            .cells
+            .iter()
+            .map(|(&index, names)| {
+                let mut bindings: Vec<&String> = names.bindings().collect();
+                bindings.sort();
+                let dependencies: Vec<String> = self
+                    .dependencies(index)
+                    .map(|edge| {
+                        format!(
+                            "{} ({}){}",
+                            edge.source + 1,
+                            edge.names.join(", "),
+                            if edge.forward { " [later]" } else { "" }
+                        )
+                    })
+                    .collect();
+                (
+                    (index + 1).to_string(),
+                    or_dash(
+                        bindings
+                            .iter()
+                            .map(|name| name.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                    ),
+                    or_dash(dependencies.join(", ")),
+                )
+            })
+            .collect();
+
+        let cell_width = rows
+            .iter()
+            .map(|row| row.0.len())
+            .max()
+            .unwrap_or(0)
+            .max("Cell".len());
+        let binds_width = rows
+            .iter()
+            .map(|row| row.1.len())
+            .max()
+            .unwrap_or(0)
+            .max("Defines".len());
+
+        writeln!(
+            f,
+            "{:<cell_width$}  {:<binds_width$}  Depends on",
+            "Cell", "Defines"
+        )?;
+        for (cell, binds, dependencies) in rows {
+            writeln!(
+                f,
+                "{:<cell_width$}  {:<binds_width$}  {}",
+                cell, binds, dependencies
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn or_dash(text: String) -> String {
+    if text.is_empty() {
+        "-".to_string()
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notebook() -> Notebook {
+        r###"{
+ "cells": [
+  {"cell_type": "markdown", "metadata": {}, "source": ["# Analysis"]},
+  {"cell_type": "code", "execution_count": 1, "metadata": {}, "outputs": [], "source": ["import numpy as np\n", "import pandas as pd"]},
+  {"cell_type": "code", "execution_count": 2, "metadata": {}, "outputs": [], "source": ["%matplotlib inline\n", "!pip install seaborn"]},
+  {"cell_type": "markdown", "metadata": {}, "source": ["## Load"]},
+  {"cell_type": "code", "execution_count": 3, "metadata": {}, "outputs": [], "source": ["df = pd.read_csv('data.csv')\n", "files = !ls"]},
+  {"cell_type": "code", "execution_count": 4, "metadata": {}, "outputs": [], "source": ["def clean(frame):\n", "    return frame.dropna()"]},
+  {"cell_type": "code", "execution_count": 5, "metadata": {}, "outputs": [], "source": ["df = clean(df)\n", "print(f\"{len(df)} rows\")"]},
+  {"cell_type": "markdown", "metadata": {}, "source": ["## Model"]},
+  {"cell_type": "code", "execution_count": 6, "metadata": {}, "outputs": [], "source": ["threshold = 0.5"]},
+  {"cell_type": "code", "execution_count": 7, "metadata": {}, "outputs": [], "source": ["scores = np.array([s for s in df.score if s > threshold])\n", "scores.mean()"]},
+  {"cell_type": "code", "execution_count": 8, "metadata": {}, "outputs": [], "source": ["%%bash\n", "echo done"]},
+  {"cell_type": "code", "execution_count": 10, "metadata": {}, "outputs": [], "source": ["summary = report(scores)"]},
+  {"cell_type": "code", "execution_count": 9, "metadata": {}, "outputs": [], "source": ["def report(values):\n", "    return {'mean': values.mean(), 'n': len(values)}"]}
+ ],
+ "metadata": {},
+ "nbformat": 4,
+ "nbformat_minor": 4
+}"###
+        .parse()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_dependency_graph() {
+        let notebook = notebook();
+        let graph = DependencyGraph::build(&notebook);
+
+        let edges: Vec<(usize, usize, Vec<&str>, bool)> = graph
+            .edges
+            .iter()
+            .map(|edge| {
+                let names = edge.names.iter().map(String::as_str).collect();
+                (edge.cell + 1, edge.source + 1, names, edge.forward)
+            })
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                (5, 2, vec!["pd"], false),
+                (7, 5, vec!["df"], false),
+                (7, 6, vec!["clean"], false),
+                (10, 2, vec!["np"], false),
+                (10, 7, vec!["df"], false),
+                (10, 9, vec!["threshold"], false),
+                (12, 10, vec!["scores"], false),
+                (12, 13, vec!["report"], true),
+            ]
+        );
+        assert!(graph.cells[&4].assigned.contains("files"));
+        assert_eq!(graph.cells[&10], Default::default());
+        assert_eq!(graph.dependents(1).count(), 2);
+    }
+
+    #[test]
+    fn test_dependency_table_and_dot() {
+        let notebook = notebook();
+        let graph = DependencyGraph::build(&notebook);
+
+        let table = graph.to_string();
+        assert!(table.starts_with("Cell  Defines    Depends on\n"));
+        assert!(table.contains("\n2     np, pd     -\n"));
+        assert!(table.contains("\n12    summary    10 (scores), 13 (report) [later]\n"));
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph dependencies {\n"));
+        assert!(dot.contains("    cell6 [label=\"Cell 6: def `clean`\"];\n"));
+        assert!(dot.contains("    cell2 -> cell10 [label=\"np\"];\n"));
+        assert!(dot.contains("    cell13 -> cell12 [label=\"report\", style=dashed];\n"));
+    }
+}
@@ -0,0 +1,126 @@
+//! Non-fatal problems found while reading and rendering a notebook.
+//!
+//! A conversion either fails with a `JupyterError` or succeeds with a
+//! `Conversion`, which carries the rendered text together with everything
+//! the converter skipped, repaired or changed on the way:
+//!
+//! | Code                  | Severity | Meaning                                          |
+//! |-----------------------|----------|--------------------------------------------------|
+//! | `truncated-json`      | warning  | the file was cut short and salvaged              |
+//! | `invalid-metadata`    | warning  | notebook metadata was not an object and dropped  |
+//! | `missing-cells`       | warning  | the notebook has no `cells` array                |
+//! | `unreadable-cell`     | warning  | a cell did not deserialize, shown as placeholder |
+//! | `unknown-cell-type`   | warning  | a cell of unknown type, shown as placeholder     |
+//! | `dropped-output`      | warning  | an output was malformed or has no text form      |
+//! | `unknown-output-type` | warning  | an output of unknown type, only its type shown   |
+//! | `unknown-mime-type`   | note     | a MIME type the converter does not know          |
+//! | `truncated`           | note     | frames or text were left out to keep it short    |
+//! | `redacted`            | note     | secrets were replaced with placeholders          |
+//! | `scrubbed`            | note     | personal data was replaced with placeholders     |
+//! | `upgrade`             | note     | a legacy layout was read as current nbformat 4   |
+//! | `reference-cycle`     | note     | a referenced file refers back, not followed      |
+//! | `reference-skipped`   | warning  | a referenced file is over a limit or unreadable  |
+//! | `data-file-skipped`   | warning  | a data file could not be read or parsed          |
+
+use std::fmt;
+use std::ops::Deref;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Note,
+    Warning,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Note => "note",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Zero-based cell index, or `None` for the notebook as a whole.
+    pub cell_index: Option<usize>,
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn note(cell_index: Option<usize>, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Note,
+            cell_index,
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn warning(
+        cell_index: Option<usize>,
+        code: &'static str,
+        message: impl Into<String>,
+    ) -> Self {
+        Self {
+            severity: Severity::Warning,
+            cell_index,
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.cell_index {
+            Some(index) => write!(f, "Cell {}: ", index + 1)?,
+            None => write!(f, "Notebook: ")?,
+        }
+        write!(f, "{} [{}] {}", self.severity, self.code, self.message)
+    }
+}
+
+/// The rendered text of a notebook and the diagnostics collected on the way.
+///
+/// Dereferences to the text, so it can be used wherever a `&str` is expected.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Conversion {
+    pub text: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Conversion {
+    pub fn into_text(self) -> String {
+        self.text
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == severity)
+            .count()
+    }
+}
+
+impl Deref for Conversion {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.text
+    }
+}
+
+impl fmt::Display for Conversion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
@@ -0,0 +1,514 @@
+//! Inventory of the packages a notebook imports and installs.
+//!
+//! Imported modules are classified as standard library, third-party or local
+//! using a bundled list of standard library modules. `pip install` and
+//! `conda install` lines record packages the notebook installs itself, and
+//! `pip freeze` outputs saved in the notebook supply the versions used for a
+//! requirements.txt draft.
+
+use crate::python;
+use crate::resolve;
+use crate::Notebook;
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Top-level modules of the Python 3 standard library, including modules
+/// removed in recent releases that older notebooks still import.
+#[rustfmt::skip]
+const STDLIB_MODULES: &[&str] = &[
+    "__future__", "abc", "aifc", "argparse", "array", "ast", "asynchat", "asyncio", "asyncore",
+    "atexit", "audioop", "base64", "bdb", "binascii", "bisect", "builtins", "bz2", "cProfile",
+    "calendar", "cgi", "cgitb", "chunk", "cmath", "cmd", "code", "codecs", "codeop", "collections",
+    "colorsys", "compileall", "concurrent", "configparser", "contextlib", "contextvars", "copy",
+    "copyreg", "crypt", "csv", "ctypes", "curses", "dataclasses", "datetime", "dbm", "decimal",
+    "difflib", "dis", "distutils", "doctest", "email", "encodings", "ensurepip", "enum", "errno",
+    "faulthandler", "fcntl", "filecmp", "fileinput", "fnmatch", "fractions", "ftplib", "functools",
+    "gc", "getopt", "getpass", "gettext", "glob", "graphlib", "grp", "gzip", "hashlib", "heapq",
+    "hmac", "html", "http", "idlelib", "imaplib", "imghdr", "imp", "importlib", "inspect", "io",
+    "ipaddress", "itertools", "json", "keyword", "lib2to3", "linecache", "locale", "logging",
+    "lzma", "mailbox", "mailcap", "marshal", "math", "mimetypes", "mmap", "modulefinder", "msilib",
+    "msvcrt", "multiprocessing", "netrc", "nis", "nntplib", "ntpath", "numbers", "opcode",
+    "operator", "optparse", "os", "ossaudiodev", "pathlib", "pdb", "pickle", "pickletools", "pipes",
+    "pkgutil", "platform", "plistlib", "poplib", "posix", "posixpath", "pprint", "profile",
+    "pstats", "pty", "pwd", "py_compile", "pyclbr", "pydoc", "pyexpat", "queue", "quopri", "random",
+    "re", "readline", "reprlib", "resource", "rlcompleter", "runpy", "sched", "secrets", "select",
+    "selectors", "shelve", "shlex", "shutil", "signal", "site", "smtpd", "smtplib", "sndhdr",
+    "socket", "socketserver", "spwd", "sqlite3", "sre_compile", "sre_constants", "sre_parse", "ssl",
+    "stat", "statistics", "string", "stringprep", "struct", "subprocess", "sunau", "symtable",
+    "sys", "sysconfig", "syslog", "tabnanny", "tarfile", "telnetlib", "tempfile", "termios",
+    "textwrap", "this", "threading", "time", "timeit", "tkinter", "token", "tokenize", "tomllib",
+    "trace", "traceback", "tracemalloc", "tty", "turtle", "types", "typing", "unicodedata",
+    "unittest", "urllib", "uu", "uuid", "venv", "warnings", "wave", "weakref", "webbrowser",
+    "winreg", "winsound", "wsgiref", "xdrlib", "xml", "xmlrpc", "zipapp", "zipfile", "zipimport",
+    "zlib", "zoneinfo",
+];
+
+/// Import names whose distribution on PyPI has a different name.
+const DISTRIBUTION_NAMES: &[(&str, &str)] = &[
+    ("Crypto", "pycryptodome"),
+    ("IPython", "ipython"),
+    ("OpenSSL", "pyOpenSSL"),
+    ("PIL", "Pillow"),
+    ("attr", "attrs"),
+    ("bs4", "beautifulsoup4"),
+    ("cv2", "opencv-python"),
+    ("dateutil", "python-dateutil"),
+    ("docx", "python-docx"),
+    ("dotenv", "python-dotenv"),
+    ("fitz", "PyMuPDF"),
+    ("jwt", "PyJWT"),
+    ("magic", "python-magic"),
+    ("serial", "pyserial"),
+    ("skimage", "scikit-image"),
+    ("sklearn", "scikit-learn"),
+    ("yaml", "PyYAML"),
+];
+
+/// Options of `pip install` and `conda install` that take a value.
+const OPTIONS_WITH_VALUE: &[&str] = &[
+    "-c",
+    "--channel",
+    "-r",
+    "--requirement",
+    "-i",
+    "--index-url",
+    "--extra-index-url",
+    "-f",
+    "--find-links",
+    "-e",
+    "--editable",
+    "-t",
+    "--target",
+    "--prefix",
+    "-n",
+    "--name",
+    "-p",
+    "--constraint",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ModuleKind {
+    Stdlib,
+    ThirdParty,
+    /// Relative imports, modules the notebook writes with `%%writefile` and
+    /// modules found next to the notebook.
+    Local,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Module {
+    pub name: String,
+    pub kind: ModuleKind,
+    /// Indices of the cells that import the module.
+    pub cells: Vec<usize>,
+}
+
+impl Module {
+    /// Returns the PyPI distribution name of a third-party module.
+    pub fn distribution(&self) -> &str {
+        DISTRIBUTION_NAMES
+            .iter()
+            .find(|(module, _)| *module == self.name)
+            .map(|(_, distribution)| *distribution)
+            .unwrap_or(&self.name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackageManager {
+    Pip,
+    Conda,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Install {
+    pub cell: usize,
+    pub manager: PackageManager,
+    pub package: String,
+    /// Version constraint as written, such as `==2.1.0` or `>=1.4`.
+    pub constraint: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Environment {
+    /// Imported modules, sorted by name.
+    pub modules: Vec<Module>,
+    pub installs: Vec<Install>,
+    /// Versions listed by `pip freeze` outputs, by normalized package name.
+    pub frozen: BTreeMap<String, String>,
+}
+
+struct Patterns {
+    install: Regex,
+    freeze_line: Regex,
+    requirement: Regex,
+}
+
+fn patterns() -> &'static Patterns {
+    static PATTERNS: OnceLock<Patterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| Patterns {
+        install: Regex::new(r"^\s*[!%]\s*(pip3?|python3?\s+-m\s+pip|conda|mamba)\s+install\b(.*)$")
+            .unwrap(),
+        freeze_line: Regex::new(r"^([A-Za-z0-9][A-Za-z0-9._-]*)==([^\s;]+)\s*$").unwrap(),
+        requirement: Regex::new(r"^([A-Za-z0-9][A-Za-z0-9._-]*)(\[[^\]]*\])?(.*)$").unwrap(),
+    })
+}
+
+/// Normalizes a package name as pip compares them.
+pub fn normalize_package_name(name: &str) -> String {
+    name.to_lowercase().replace(['_', '.'], "-")
+}
+
+impl Environment {
+    pub fn scan(notebook: &Notebook) -> Self {
+        let mut environment = Self::default();
+        let mut modules: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        let mut written_modules = Vec::new();
+
+        for (index, cell) in notebook.cells.iter().enumerate() {
+            if cell.cell_type != "code" {
+                continue;
+            }
+            let lines: Vec<&str> = cell.source_lines().collect();
+
+            if let Some(path) = lines
+                .iter()
+                .find(|line| !line.trim().is_empty())
+                .and_then(|line| line.trim().strip_prefix("%%writefile"))
+            {
+                written_modules.extend(written_module(path));
+            }
+
+            for module in python::analyze_cell(cell).modules {
+                modules.entry(module).or_default().push(index);
+            }
+            for line in &lines {
+                environment.installs.extend(parse_install(line, index));
+            }
+
+            let runs_freeze = lines.iter().any(|line| {
+                line.contains("pip freeze") || line.contains("pip list --format=freeze")
+            });
+            if runs_freeze {
+                for output in cell.outputs.iter().flatten() {
+                    for line in output.text.iter().flatten().flat_map(|text| text.lines()) {
+                        if let Some(captures) = patterns().freeze_line.captures(line.trim()) {
+                            environment.frozen.insert(
+                                normalize_package_name(&captures[1]),
+                                captures[2].to_string(),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        environment.modules = modules
+            .into_iter()
+            .map(|(name, cells)| {
+                let kind = if name.starts_with('.') || written_modules.contains(&name) {
+                    ModuleKind::Local
+                } else if STDLIB_MODULES.contains(&name.as_str()) {
+                    ModuleKind::Stdlib
+                } else {
+                    ModuleKind::ThirdParty
+                };
+                Module { name, kind, cells }
+            })
+            .collect();
+        environment
+    }
+
+    /// Reclassifies imports of modules that exist in `dir`, such as `utils`
+    /// for a `utils.py` beside the notebook, as local.
+    pub fn with_local_modules(mut self, dir: &Path) -> Self {
+        for module in &mut self.modules {
+            if module.kind == ModuleKind::ThirdParty
+                && resolve::module_path(dir, &module.name).is_some()
+            {
+                module.kind = ModuleKind::Local;
+            }
+        }
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.modules.is_empty() && self.installs.is_empty()
+    }
+
+    pub fn modules_of_kind(&self, kind: ModuleKind) -> impl Iterator<Item = &Module> {
+        self.modules
+            .iter()
+            .filter(move |module| module.kind == kind)
+    }
+
+    /// Drafts a requirements.txt from third-party imports and installed
+    /// packages. Versions come from `pip freeze` outputs first, then from the
+    /// constraints of install lines.
+    pub fn requirements(&self) -> String {
+        let mut requirements: BTreeMap<String, String> = BTreeMap::new();
+
+        for install in &self.installs {
+            let key = normalize_package_name(&install.package);
+            let requirement = match self.frozen.get(&key) {
+                Some(version) => format!("{}=={}", install.package, version),
+                None => format!("{}{}", install.package, install.constraint),
+            };
+            requirements.entry(key).or_insert(requirement);
+        }
+
+        for module in self.modules_of_kind(ModuleKind::ThirdParty) {
+            let distribution = module.distribution();
+            let key = normalize_package_name(distribution);
+            let requirement = match self.frozen.get(&key) {
+                Some(version) => format!("{}=={}", distribution, version),
+                None => distribution.to_string(),
+            };
+            requirements.entry(key).or_insert(requirement);
+        }
+
+        let mut draft =
+            String::from("# Draft generated by jupyter2llm from notebook imports and installs\n");
+        for requirement in requirements.values() {
+            draft.push_str(requirement);
+            draft.push('\n');
+        }
+        draft
+    }
+}
+
+/// Returns the module a `%%writefile` cell creates, such as `utils` for
+/// `utils.py` or `pkg` for `pkg/__init__.py`.
+fn written_module(path: &str) -> Option<String> {
+    let path = path
+        .split_whitespace()
+        .rfind(|arg| !arg.starts_with('-'))?
+        .trim_matches(['"', '\'']);
+    let path = path.strip_suffix(".py")?;
+    let module = path
+        .trim_start_matches("./")
+        .split(['/', '\\'])
+        .next()?
+        .to_string();
+    python::is_identifier(&module).then_some(module)
+}
+
+/// Parses the packages of a `!pip install` or `%conda install` line.
+fn parse_install(line: &str, cell: usize) -> Vec<Install> {
+    let Some(captures) = patterns().install.captures(line) else {
+        return Vec::new();
+    };
+    let manager = if captures[1].contains("pip") {
+        PackageManager::Pip
+    } else {
+        PackageManager::Conda
+    };
+
+    let mut installs = Vec::new();
+    let mut arguments = captures[2].split_whitespace();
+    while let Some(argument) = arguments.next() {
+        if ["&&", "||", ";", "|", ">", "2>&1"].contains(&argument) || argument.starts_with('#') {
+            break;
+        }
+        if OPTIONS_WITH_VALUE.contains(&argument) {
+            arguments.next();
+            continue;
+        }
+        let argument = argument.trim_matches(['"', '\'']);
+        if argument.starts_with('-') || argument.contains('/') || argument.starts_with('.') {
+            continue;
+        }
+        let Some(requirement) = patterns().requirement.captures(argument) else {
+            continue;
+        };
+        let mut constraint = requirement[3].to_string();
+        if manager == PackageManager::Conda
+            && constraint.starts_with('=')
+            && !constraint.starts_with("==")
+        {
+            constraint.insert(0, '=');
+        }
+        installs.push(Install {
+            cell,
+            manager,
+            package: requirement[1].to_string(),
+            constraint,
+        });
+    }
+    installs
+}
+
+impl fmt::Display for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "**Environment**:")?;
+
+        let groups = [
+            (ModuleKind::Stdlib, "Standard library"),
+            (ModuleKind::ThirdParty, "Third-party"),
+            (ModuleKind::Local, "Local"),
+        ];
+        for (kind, label) in groups {
+            let modules: Vec<String> = self
+                .modules_of_kind(kind)
+                .map(|module| {
+                    let distribution = module.distribution();
+                    let version = self.frozen.get(&normalize_package_name(distribution));
+                    match (kind, version) {
+                        (ModuleKind::ThirdParty, Some(version)) if distribution != module.name => {
+                            format!("{} ({} {})", module.name, distribution, version)
+                        }
+                        (ModuleKind::ThirdParty, Some(version)) => {
+                            format!("{} {}", module.name, version)
+                        }
+                        (ModuleKind::ThirdParty, None) if distribution != module.name => {
+                            format!("{} ({})", module.name, distribution)
+                        }
+                        _ => module.name.clone(),
+                    }
+                })
+                .collect();
+            if !modules.is_empty() {
+                writeln!(f, "- {}: {}", label, modules.join(", "))?;
+            }
+        }
+
+        if !self.installs.is_empty() {
+            let installs: Vec<String> = self
+                .installs
+                .iter()
+                .map(|install| {
+                    format!(
+                        "{}{} (cell {})",
+                        install.package,
+                        install.constraint,
+                        install.cell + 1
+                    )
+                })
+                .collect();
+            writeln!(f, "- Installed by the notebook: {}", installs.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_install() {
+        let installs = parse_install(
+            "!python -m pip install -U 'pandas[excel]>=2.0' -i https://mirror/simple seaborn && echo ok",
+            0,
+        );
+        let specs: Vec<(&str, &str)> = installs
+            .iter()
+            .map(|install| (install.package.as_str(), install.constraint.as_str()))
+            .collect();
+        assert_eq!(specs, vec![("pandas", ">=2.0"), ("seaborn", "")]);
+
+        let installs = parse_install("%conda install -c conda-forge numpy=1.26 ./local", 3);
+        assert_eq!(installs[0].manager, PackageManager::Conda);
+        assert_eq!(installs[0].constraint, "==1.26");
+        assert_eq!(installs.len(), 1);
+        assert!(parse_install("pip install numpy", 0).is_empty());
+    }
+
+    #[test]
+    fn test_written_module() {
+        assert_eq!(written_module(" utils.py"), Some("utils".to_string()));
+        assert_eq!(
+            written_module(" -a pkg/__init__.py"),
+            Some("pkg".to_string())
+        );
+        assert_eq!(written_module(" config.yaml"), None);
+    }
+
+    #[test]
+    fn test_local_modules_beside_the_notebook() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("pipeline")).unwrap();
+        std::fs::write(dir.path().join("pipeline/__init__.py"), "").unwrap();
+        std::fs::write(dir.path().join("utils.py"), "").unwrap();
+
+        let notebook: Notebook = r#"{"cells": [{"cell_type": "code", "execution_count": 1,
+            "metadata": {}, "outputs": [],
+            "source": ["import numpy\n", "import utils\n", "from pipeline import steps\n"]}],
+            "metadata": {}, "nbformat": 4, "nbformat_minor": 4}"#
+            .parse()
+            .unwrap();
+        let environment = Environment::scan(&notebook).with_local_modules(dir.path());
+
+        let kinds: Vec<(&str, ModuleKind)> = environment
+            .modules
+            .iter()
+            .map(|module| (module.name.as_str(), module.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("numpy", ModuleKind::ThirdParty),
+                ("pipeline", ModuleKind::Local),
+                ("utils", ModuleKind::Local)
+            ]
+        );
+        assert!(!environment.requirements().contains("utils"));
+    }
+
+    fn notebook() -> Notebook {
+        r##"{
+ "cells": [
+  {"cell_type": "code", "execution_count": 1, "metadata": {}, "outputs": [], "source": ["%pip install -q torch==2.1.0 'transformers>=4.30' -r requirements.txt\n", "!conda install -c conda-forge -y lightgbm=4.1"]},
+  {"cell_type": "code", "execution_count": 2, "metadata": {}, "outputs": [], "source": ["import os, json\n", "import numpy as np\n", "from sklearn.model_selection import train_test_split\n", "from .helpers import load\n", "import utils"]},
+  {"cell_type": "code", "execution_count": 3, "metadata": {}, "outputs": [{"output_type": "stream", "name": "stdout", "text": ["Writing utils.py\n"]}], "source": ["%%writefile utils.py\n", "def helper():\n", "    return 1"]},
+  {"cell_type": "code", "execution_count": 4, "metadata": {}, "outputs": [{"output_type": "stream", "name": "stdout", "text": ["numpy==1.26.4\n", "scikit-learn==1.4.0\n"]}], "source": ["!pip freeze | grep -E 'numpy|scikit'"]}
+ ],
+ "metadata": {},
+ "nbformat": 4,
+ "nbformat_minor": 4
+}"##
+        .parse()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_module_kinds() {
+        let environment = Environment::scan(&notebook());
+
+        let names = |kind| -> Vec<String> {
+            environment
+                .modules_of_kind(kind)
+                .map(|module| module.name.clone())
+                .collect()
+        };
+        assert_eq!(names(ModuleKind::Stdlib), vec!["json", "os"]);
+        assert_eq!(names(ModuleKind::ThirdParty), vec!["numpy", "sklearn"]);
+        assert_eq!(names(ModuleKind::Local), vec![".helpers", "utils"]);
+    }
+
+    #[test]
+    fn test_requirements_draft() {
+        let requirements = Environment::scan(&notebook()).requirements();
+
+        assert_eq!(
+            requirements,
+            "# Draft generated by jupyter2llm from notebook imports and installs\n\
+             lightgbm==4.1\n\
+             numpy==1.26.4\n\
+             scikit-learn==1.4.0\n\
+             torch==2.1.0\n\
+             transformers>=4.30\n"
+        );
+
+        let plain: Notebook = r#"{
+ "cells": [{"cell_type": "code", "execution_count": 1, "metadata": {}, "outputs": [], "source": ["x = 1"]}],
+ "metadata": {}, "nbformat": 4, "nbformat_minor": 4
+}"#
+        .parse()
+        .unwrap();
+        assert!(Environment::scan(&plain).is_empty());
+    }
+}
@@ -0,0 +1,200 @@
+//! Execution-order analysis over `Cell.execution_count`.
+//!
+//! Saved outputs only match a top-to-bottom reading when the cells were run in
+//! document order. This pass detects cells that ran out of order, gaps in the
+//! execution counts, duplicate counts and code cells that never ran.
+
+use crate::Notebook;
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CellOrder {
+    /// Render cells as they appear in the notebook.
+    #[default]
+    Document,
+    /// Render executed code cells by execution count. Markdown and raw cells
+    /// stay with the code cell that follows them; code cells that never ran
+    /// go last.
+    Execution,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecutionAnalysis {
+    /// Number of code cells with an execution count.
+    pub executed: usize,
+    /// Cells whose execution count is lower than that of an earlier cell.
+    pub out_of_order: Vec<usize>,
+    /// Inclusive ranges of execution counts that appear on no cell.
+    pub gaps: Vec<(u32, u32)>,
+    /// Non-empty code cells without an execution count.
+    pub never_executed: Vec<usize>,
+    /// Execution counts shared by several cells, with those cells.
+    pub duplicates: Vec<(u32, Vec<usize>)>,
+}
+
+impl ExecutionAnalysis {
+    pub fn analyze(notebook: &Notebook) -> Self {
+        let mut analysis = Self::default();
+        let mut by_count: BTreeMap<u32, Vec<usize>> = BTreeMap::new();
+        let mut highest_so_far: Option<u32> = None;
+
+        for (index, cell) in notebook.cells.iter().enumerate() {
+            if cell.cell_type != "code" {
+                continue;
+            }
+            match cell.execution_count {
+                Some(count) => {
+                    analysis.executed += 1;
+                    by_count.entry(count).or_default().push(index);
+                    if highest_so_far.is_some_and(|highest| count < highest) {
+                        analysis.out_of_order.push(index);
+                    }
+                    highest_so_far = highest_so_far.max(Some(count));
+                }
+                None => {
+                    if cell.source_lines().any(|line| !line.trim().is_empty()) {
+                        analysis.never_executed.push(index);
+                    }
+                }
+            }
+        }
+
+        let mut expected = 1;
+        for (&count, cells) in &by_count {
+            if count > expected {
+                analysis.gaps.push((expected, count - 1));
+            }
+            expected = count + 1;
+            if cells.len() > 1 {
+                analysis.duplicates.push((count, cells.clone()));
+            }
+        }
+
+        analysis
+    }
+
+    /// True when every executed cell ran once, in document order, without gaps.
+    pub fn is_linear(&self) -> bool {
+        self.out_of_order.is_empty() && self.gaps.is_empty() && self.duplicates.is_empty()
+    }
+
+    /// Returns the cell indices in execution order, see [`CellOrder::Execution`].
+    pub fn execution_order(notebook: &Notebook) -> Vec<usize> {
+        let mut keys = vec![u32::MAX; notebook.cells.len()];
+        let mut next_count = u32::MAX;
+
+        for (index, cell) in notebook.cells.iter().enumerate().rev() {
+            keys[index] = match (cell.cell_type.as_str(), cell.execution_count) {
+                ("code", Some(count)) => {
+                    next_count = count;
+                    count
+                }
+                ("code", None) => {
+                    next_count = u32::MAX;
+                    u32::MAX
+                }
+                _ => next_count,
+            };
+        }
+
+        let mut order: Vec<usize> = (0..notebook.cells.len()).collect();
+        order.sort_by_key(|&index| keys[index]);
+        order
+    }
+}
+
+fn cell_list(cells: &[usize]) -> String {
+    cells
+        .iter()
+        .map(|index| (index + 1).to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl fmt::Display for ExecutionAnalysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.executed == 0 {
+            return writeln!(f, "**Execution Order**: never executed");
+        }
+        if self.is_linear() && self.never_executed.is_empty() {
+            return writeln!(f, "**Execution Order**: linear");
+        }
+
+        if self.is_linear() {
+            writeln!(f, "**Execution Order**: linear, with unexecuted cells")?;
+        } else {
+            writeln!(
+                f,
+                "**Execution Order**: not linear, outputs may not match a top-to-bottom reading"
+            )?;
+        }
+        if !self.out_of_order.is_empty() {
+            writeln!(
+                f,
+                "- Ran before earlier cells: cells {}",
+                cell_list(&self.out_of_order)
+            )?;
+        }
+        if !self.gaps.is_empty() {
+            let gaps: Vec<String> = self
+                .gaps
+                .iter()
+                .map(|&(start, end)| {
+                    if start == end {
+                        start.to_string()
+                    } else {
+                        format!("{}-{}", start, end)
+                    }
+                })
+                .collect();
+            writeln!(f, "- Missing execution counts: {}", gaps.join(", "))?;
+        }
+        for (count, cells) in &self.duplicates {
+            writeln!(
+                f,
+                "- Execution count {} on cells {}",
+                count,
+                cell_list(cells)
+            )?;
+        }
+        if !self.never_executed.is_empty() {
+            writeln!(
+                f,
+                "- Never executed: cells {}",
+                cell_list(&self.never_executed)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_execution_analysis() {
+        let notebook: Notebook = r#"{
+ "cells": [
+  {"cell_type": "markdown", "metadata": {}, "source": ["Load the data"]},
+  {"cell_type": "code", "execution_count": 7, "metadata": {}, "outputs": [], "source": ["df = load()"]},
+  {"cell_type": "markdown", "metadata": {}, "source": ["Configure"]},
+  {"cell_type": "code", "execution_count": 2, "metadata": {}, "outputs": [], "source": ["path = 'a.csv'"]},
+  {"cell_type": "code", "execution_count": 2, "metadata": {}, "outputs": [], "source": ["limit = 10"]},
+  {"cell_type": "code", "execution_count": null, "metadata": {}, "outputs": [], "source": ["plot(df)"]},
+  {"cell_type": "code", "execution_count": 8, "metadata": {}, "outputs": [], "source": ["df.head()"]}
+ ],
+ "metadata": {}, "nbformat": 4, "nbformat_minor": 4
+}"#
+        .parse()
+        .unwrap();
+        let analysis = ExecutionAnalysis::analyze(&notebook);
+
+        assert_eq!(analysis.out_of_order, vec![3, 4]);
+        assert_eq!(analysis.gaps, vec![(1, 1), (3, 6)]);
+        assert_eq!(analysis.duplicates, vec![(2, vec![3, 4])]);
+        assert_eq!(analysis.never_executed, vec![5]);
+        assert!(!analysis.is_linear());
+    }
+}
@@ -0,0 +1,189 @@
+//! Low-memory loading of large notebooks.
+//!
+//! Notebooks of hundreds of megabytes are mostly base64 images and other
+//! binary MIME payloads, which the converter never renders. This loader
+//! reads the file through a buffered reader instead of into one `String`,
+//! converts each cell as soon as it is parsed and skips binary payloads
+//! without allocating them. A skipped payload keeps its MIME type with a
+//! `null` value, so diagnostics can still name what was left out.
+
+use crate::redact::is_textual_mime;
+use crate::{Cell, JupyterError, Notebook, Output};
+use serde::de::{Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// Loads a notebook file without holding the whole file or its binary
+/// payloads in memory.
+///
+/// On a parse error the file is read again in full to locate the error.
+pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Notebook, JupyterError> {
+    let file = File::open(path.as_ref())?;
+    match from_reader(BufReader::new(file)) {
+        Ok(notebook) => Ok(notebook),
+        Err(err) => {
+            let content = std::fs::read_to_string(path)?;
+            match content.parse::<Notebook>() {
+                Err(located) => Err(located),
+                Ok(_) => Err(err),
+            }
+        }
+    }
+}
+
+/// Parses a notebook from a reader, skipping binary MIME payloads.
+pub fn from_reader<R: Read>(reader: R) -> Result<Notebook, JupyterError> {
+    let notebook: LeanNotebook = serde_json::from_reader(reader)?;
+    Ok(Notebook {
+        cells: notebook.cells.0,
+        metadata: notebook.metadata,
+        nbformat: notebook.nbformat,
+        nbformat_minor: notebook.nbformat_minor,
+    })
+}
+
+#[derive(Deserialize)]
+struct LeanNotebook {
+    cells: LeanCells,
+    metadata: HashMap<String, Value>,
+    nbformat: u32,
+    nbformat_minor: u32,
+}
+
+/// The cells array, converted into the model one cell at a time.
+struct LeanCells(Vec<Cell>);
+
+impl<'de> Deserialize<'de> for LeanCells {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct CellsVisitor;
+
+        impl<'de> Visitor<'de> for CellsVisitor {
+            type Value = LeanCells;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an array of cells")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<LeanCells, A::Error> {
+                let mut cells = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(cell) = seq.next_element::<LeanCell>()? {
+                    cells.push(cell.into());
+                }
+                Ok(LeanCells(cells))
+            }
+        }
+
+        deserializer.deserialize_seq(CellsVisitor)
+    }
+}
+
+#[derive(Deserialize)]
+struct LeanCell {
+    cell_type: String,
+    #[serde(deserialize_with = "crate::multiline")]
+    source: Vec<String>,
+    metadata: HashMap<String, Value>,
+    outputs: Option<Vec<LeanOutput>>,
+    execution_count: Option<u32>,
+}
+
+impl From<LeanCell> for Cell {
+    fn from(cell: LeanCell) -> Self {
+        Cell {
+            cell_type: cell.cell_type,
+            source: cell.source,
+            metadata: cell.metadata,
+            outputs: cell
+                .outputs
+                .map(|outputs| outputs.into_iter().map(Output::from).collect()),
+            execution_count: cell.execution_count,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct LeanOutput {
+    output_type: String,
+    #[serde(default, deserialize_with = "crate::optional_multiline")]
+    text: Option<Vec<String>>,
+    data: Option<MimeBundle>,
+    execution_count: Option<u32>,
+    ename: Option<String>,
+    evalue: Option<String>,
+    traceback: Option<Vec<String>>,
+}
+
+impl From<LeanOutput> for Output {
+    fn from(output: LeanOutput) -> Self {
+        Output {
+            output_type: output.output_type,
+            text: output.text,
+            data: output.data.map(|bundle| bundle.0),
+            execution_count: output.execution_count,
+            ename: output.ename,
+            evalue: output.evalue,
+            traceback: output.traceback,
+        }
+    }
+}
+
+/// A MIME bundle whose binary payloads are skipped while parsing.
+struct MimeBundle(HashMap<String, Value>);
+
+impl<'de> Deserialize<'de> for MimeBundle {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BundleVisitor;
+
+        impl<'de> Visitor<'de> for BundleVisitor {
+            type Value = MimeBundle;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a MIME bundle")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<MimeBundle, A::Error> {
+                let mut bundle = HashMap::new();
+                while let Some(mime) = map.next_key::<String>()? {
+                    let value = if is_textual_mime(&mime) || mime == "traceback" {
+                        map.next_value::<Value>()?
+                    } else {
+                        map.next_value::<IgnoredAny>()?;
+                        Value::Null
+                    };
+                    bundle.insert(mime, value);
+                }
+                Ok(MimeBundle(bundle))
+            }
+        }
+
+        deserializer.deserialize_map(BundleVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binary_payloads_are_skipped() {
+        let content = r#"{
+ "cells": [{"cell_type": "code", "execution_count": 1, "metadata": {}, "source": ["plot()"],
+   "outputs": [{"output_type": "display_data", "metadata": {},
+     "data": {"image/png": "iVBORw0KGgo=", "text/plain": ["<Figure>"], "image/svg+xml": ["<svg/>"]}}]}],
+ "metadata": {}, "nbformat": 4, "nbformat_minor": 4
+}"#;
+        let notebook = from_reader(content.as_bytes()).unwrap();
+        let data = notebook.cells[0].outputs.as_ref().unwrap()[0]
+            .data
+            .as_ref()
+            .unwrap();
+        assert_eq!(data["image/png"], Value::Null);
+        assert_eq!(data["text/plain"], serde_json::json!(["<Figure>"]));
+        assert_eq!(data["image/svg+xml"], serde_json::json!(["<svg/>"]));
+    }
+}
@@ -1,21 +1,114 @@
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::io::Write;
 use std::path::Path;
+use std::str::FromStr;
 use thiserror::Error;
 
+pub mod anonymize;
+pub mod batch;
+pub mod borrowed;
+pub mod bundle;
+pub mod deps;
+pub mod diagnostic;
+pub mod environment;
+pub mod execution;
+pub mod lean;
+pub mod lint;
+pub mod location;
+pub mod outline;
+pub mod pii;
+pub mod python;
+pub mod recovery;
+pub mod redact;
+pub mod resolve;
+pub mod script;
+pub mod sections;
+pub mod sniff;
+pub mod traceback;
+pub mod validate;
+pub mod view;
+
+use anonymize::Anonymizer;
+use borrowed::BorrowedNotebook;
+use deps::DependencyGraph;
+use diagnostic::{Conversion, Diagnostic};
+use environment::Environment;
+use execution::{CellOrder, ExecutionAnalysis};
+use location::ErrorLocation;
+use pii::PiiScrubber;
+use redact::Redactor;
+use resolve::ReferenceLimits;
+use sniff::DataLimits;
+use traceback::TracebackMode;
+use validate::ValidationLevel;
+use view::{CellView, OutputView};
+
 #[derive(Error, Debug)]
 pub enum JupyterError {
     #[error("Failed to read notebook file: {0}")]
     FileReadError(#[from] std::io::Error),
 
-    #[error("Failed to parse JSON: {0}")]
-    JsonParseError(#[from] serde_json::Error),
+    #[error("Failed to write output: {0}")]
+    WriteError(std::io::Error),
+
+    #[error("Failed to parse JSON: {source}{}", location::describe(.location))]
+    JsonParseError {
+        source: serde_json::Error,
+        location: Option<Box<ErrorLocation>>,
+    },
+
+    #[error("Invalid notebook format: {message}{}", location::describe(.location))]
+    InvalidNotebookFormat {
+        message: String,
+        location: Option<Box<ErrorLocation>>,
+    },
+
+    #[error("Notebook cell has invalid type: {cell_type}{}", location::describe(.location))]
+    InvalidCellType {
+        cell_type: String,
+        location: Option<Box<ErrorLocation>>,
+    },
+
+    #[error("Section not found: {0}")]
+    SectionNotFound(String),
+
+    #[error("Cell not found: {0}")]
+    CellNotFound(usize),
 
-    #[error("Invalid notebook format: {0}")]
-    InvalidNotebookFormat(String),
+    #[error("Invalid pattern: {0}")]
+    InvalidPattern(String),
+
+    #[error("Invalid lint configuration: {0}")]
+    InvalidLintConfig(String),
+
+    #[error("No notebooks found for input: {0}")]
+    InputNotFound(String),
+
+    #[error("Output path is already used by another notebook: {0}")]
+    OutputConflict(String),
+}
+
+impl JupyterError {
+    /// Where in the notebook the error occurred, when it is known.
+    pub fn location(&self) -> Option<&ErrorLocation> {
+        match self {
+            JupyterError::JsonParseError { location, .. }
+            | JupyterError::InvalidNotebookFormat { location, .. }
+            | JupyterError::InvalidCellType { location, .. } => location.as_deref(),
+            _ => None,
+        }
+    }
+}
 
-    #[error("Notebook cell has invalid type: {0}")]
-    InvalidCellType(String),
+impl From<serde_json::Error> for JupyterError {
+    fn from(source: serde_json::Error) -> Self {
+        JupyterError::JsonParseError {
+            source,
+            location: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,37 +119,199 @@ pub struct Notebook {
     pub nbformat_minor: u32,
 }
 
+impl Notebook {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, JupyterError> {
+        let content = std::fs::read_to_string(path)?;
+        content.parse()
+    }
+}
+
+impl FromStr for Notebook {
+    type Err = JupyterError;
+
+    fn from_str(content: &str) -> Result<Self, Self::Err> {
+        let err = match serde_json::from_str(content) {
+            Ok(notebook) => return Ok(notebook),
+            Err(err) => err,
+        };
+        // Point at the offending key instead of the opaque serde message. The
+        // JSON tree is only built for that.
+        let value: serde_json::Value =
+            serde_json::from_str(content).map_err(|err| location::parse_error(err, content))?;
+        let err = location::parse_error(err, content);
+        let failed = err
+            .location()
+            .and_then(|location| location.pointer.as_deref())
+            .unwrap_or_default();
+
+        // The issue at the value serde stopped on, or at the innermost object
+        // enclosing it for a missing key, is the cause; other issues are not.
+        let cause = validate::Validator::new(ValidationLevel::Lenient)
+            .validate_value(&value)
+            .into_iter()
+            .filter(|issue| {
+                failed == issue.pointer || failed.starts_with(&format!("{}/", issue.pointer))
+            })
+            .min_by_key(|issue| std::cmp::Reverse(issue.pointer.len()));
+        match cause {
+            Some(issue) => Err(JupyterError::InvalidNotebookFormat {
+                location: Some(Box::new(ErrorLocation::at_pointer(content, &issue.pointer))),
+                message: issue.message,
+            }),
+            None => Err(err),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Cell {
     pub cell_type: String,
+    #[serde(deserialize_with = "multiline")]
     pub source: Vec<String>,
     pub metadata: HashMap<String, serde_json::Value>,
     pub outputs: Option<Vec<Output>>,
     pub execution_count: Option<u32>,
 }
 
+impl Cell {
+    /// Returns true if any output of the cell is an `error` output.
+    pub fn has_error(&self) -> bool {
+        self.outputs
+            .iter()
+            .flatten()
+            .any(|output| output.output_type == "error")
+    }
+
+    /// Iterates over the logical lines of the cell source.
+    ///
+    /// Source entries usually end with `\n`, but hand-written notebooks often
+    /// store one line per entry without it; both layouts yield the same lines.
+    pub fn source_lines(&self) -> impl Iterator<Item = &str> {
+        self.source
+            .iter()
+            .flat_map(|entry| entry.strip_suffix('\n').unwrap_or(entry).split('\n'))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Output {
     pub output_type: String,
+    #[serde(default, deserialize_with = "optional_multiline")]
     pub text: Option<Vec<String>>,
     pub data: Option<HashMap<String, serde_json::Value>>,
     pub execution_count: Option<u32>,
+    pub ename: Option<String>,
+    pub evalue: Option<String>,
+    pub traceback: Option<Vec<String>>,
 }
 
-pub struct JupyterConverter {
-    include_outputs: bool,
-    include_metadata: bool,
+impl Output {
+    /// Returns the traceback of an `error` output. nbformat stores it at the
+    /// top level, but some producers nest it under `data`.
+    pub fn traceback_lines(&self) -> Option<Vec<&str>> {
+        if let Some(traceback) = &self.traceback {
+            return Some(traceback.iter().map(String::as_str).collect());
+        }
+        let traceback = self.data.as_ref()?.get("traceback")?;
+        Some(
+            traceback
+                .as_array()
+                .map(|items| items.iter().filter_map(|item| item.as_str()).collect())
+                .unwrap_or_default(),
+        )
+    }
 }
 
-impl Default for JupyterConverter {
-    fn default() -> Self {
-        Self {
-            include_outputs: false,
-            include_metadata: false,
+/// An nbformat multiline string: either one string or an array of lines.
+/// A single string is split into lines that keep their `\n`, as front ends
+/// store them.
+struct Multiline(Vec<String>);
+
+impl<'de> Deserialize<'de> for Multiline {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct MultilineVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for MultilineVisitor {
+            type Value = Multiline;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a string or an array of strings")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, text: &str) -> Result<Multiline, E> {
+                Ok(Multiline(
+                    text.split_inclusive('\n').map(str::to_string).collect(),
+                ))
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> Result<Multiline, A::Error> {
+                let mut lines = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(line) = seq.next_element::<String>()? {
+                    lines.push(line);
+                }
+                Ok(Multiline(lines))
+            }
         }
+
+        deserializer.deserialize_any(MultilineVisitor)
     }
 }
 
+pub(crate) fn multiline<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+    Ok(Multiline::deserialize(deserializer)?.0)
+}
+
+pub(crate) fn optional_multiline<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<String>>, D::Error> {
+    Ok(Option::<Multiline>::deserialize(deserializer)?.map(|lines| lines.0))
+}
+
+/// MIME types of rich outputs that notebook front ends commonly produce. Only
+/// `text/plain` is rendered; the others are skipped without a diagnostic.
+const KNOWN_MIME_TYPES: &[&str] = &[
+    "text/plain",
+    "text/html",
+    "text/markdown",
+    "text/latex",
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/svg+xml",
+    "application/json",
+    "application/javascript",
+    "application/pdf",
+    "application/vnd.jupyter.widget-view+json",
+];
+
+/// Number of preceding code cells shown with each failing cell in
+/// errors-only mode.
+pub const DEFAULT_ERROR_CONTEXT: usize = 2;
+
+#[derive(Default)]
+pub struct JupyterConverter {
+    include_outputs: bool,
+    include_metadata: bool,
+    include_outline: bool,
+    section: Option<String>,
+    context_for: Option<usize>,
+    redactor: Option<Redactor>,
+    pii_scrubber: Option<PiiScrubber>,
+    anonymizer: Option<Anonymizer>,
+    traceback_mode: TracebackMode,
+    errors_only: bool,
+    error_context: Option<usize>,
+    cell_order: CellOrder,
+    recover: bool,
+    references: Option<ReferenceLimits>,
+    data_files: Option<DataLimits>,
+}
+
 impl JupyterConverter {
     pub fn new() -> Self {
         Self::default()
@@ -72,37 +327,484 @@ impl JupyterConverter {
         self
     }
 
-    pub fn convert_file<P: AsRef<Path>>(&self, path: P) -> Result<String, JupyterError> {
-        let content = std::fs::read_to_string(path)?;
-        self.convert_str(&content)
+    /// Adds a table of contents of headings and code cells to the header.
+    pub fn with_outline(mut self, include: bool) -> Self {
+        self.include_outline = include;
+        self
     }
 
-    pub fn convert_str(&self, content: &str) -> Result<String, JupyterError> {
-        let notebook: Notebook = serde_json::from_str(content)?;
-        self.convert_notebook(&notebook)
+    /// Restricts the conversion to one section, addressed by its heading
+    /// path such as `"Training/Evaluation"`.
+    pub fn with_section<S: Into<String>>(mut self, path: S) -> Self {
+        self.section = Some(path.into());
+        self
     }
 
-    pub fn convert_notebook(&self, notebook: &Notebook) -> Result<String, JupyterError> {
-        let mut output = String::new();
+    /// Restricts the conversion to one code cell, by its 1-based number, and
+    /// the cells it transitively depends on through imported and defined
+    /// names, each with the markdown cells directly above it.
+    pub fn with_context_for(mut self, cell_number: usize) -> Self {
+        self.context_for = Some(cell_number);
+        self
+    }
 
-        // Add notebook metadata if requested
-        if self.include_metadata {
-            output.push_str(&self.format_metadata(notebook));
-            output.push_str("\n\n");
+    /// Redacts secrets from sources, outputs and metadata before rendering.
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = Some(redactor);
+        self
+    }
+
+    /// Scrubs personal data from outputs before rendering.
+    pub fn with_pii_scrubber(mut self, scrubber: PiiScrubber) -> Self {
+        self.pii_scrubber = Some(scrubber);
+        self
+    }
+
+    /// Rewrites home directories, environment prefixes and identities in
+    /// outputs and metadata before rendering.
+    pub fn with_anonymizer(mut self, anonymizer: Anonymizer) -> Self {
+        self.anonymizer = Some(anonymizer);
+        self
+    }
+
+    /// Selects how tracebacks of `error` outputs are rendered.
+    pub fn with_traceback_mode(mut self, mode: TracebackMode) -> Self {
+        self.traceback_mode = mode;
+        self
+    }
+
+    /// Renders only cells with `error` outputs, with their tracebacks, the
+    /// preceding code cells and the notebook metadata, under a one-line
+    /// summary of all failures.
+    pub fn with_errors_only(mut self, errors_only: bool) -> Self {
+        self.errors_only = errors_only;
+        self
+    }
+
+    /// Sets how many preceding code cells accompany each failing cell in
+    /// errors-only mode (default [`DEFAULT_ERROR_CONTEXT`]).
+    pub fn with_error_context(mut self, cells: usize) -> Self {
+        self.error_context = Some(cells);
+        self
+    }
+
+    /// Renders cells in document order (default) or in execution order.
+    pub fn with_cell_order(mut self, order: CellOrder) -> Self {
+        self.cell_order = order;
+        self
+    }
+
+    /// Parses notebooks leniently and renders unknown or malformed cells as
+    /// labeled placeholders instead of failing. What had to be repaired is
+    /// reported in the diagnostics of the conversion.
+    pub fn with_recovery(mut self, recover: bool) -> Self {
+        self.recover = recover;
+        self
+    }
+
+    /// Appends the local modules and `%run`/`%load` targets of converted
+    /// files, up to the given depth and size; see the `resolve` module.
+    pub fn with_references(mut self, limits: ReferenceLimits) -> Self {
+        self.references = Some(limits);
+        self
+    }
+
+    /// Appends previews of the data files that code cells name in string
+    /// literals; see the `sniff` module.
+    pub fn with_data_files(mut self, limits: DataLimits) -> Self {
+        self.data_files = Some(limits);
+        self
+    }
+
+    /// Converts a notebook file. Outside of recovery mode the file is
+    /// streamed and binary output payloads are skipped while loading; see
+    /// the `lean` module.
+    pub fn convert_file<P: AsRef<Path>>(&self, path: P) -> Result<Conversion, JupyterError> {
+        self.convert_file_counted(path.as_ref())
+            .map(|(conversion, _)| conversion)
+    }
+
+    /// Like `convert_file`, also returning the number of cells in the
+    /// notebook. The appendix is rendered from the same loaded notebook.
+    pub(crate) fn convert_file_counted(
+        &self,
+        path: &Path,
+    ) -> Result<(Conversion, usize), JupyterError> {
+        let (notebook, mut diagnostics) = if self.recover {
+            let content = std::fs::read_to_string(path)?;
+            recovery::parse_lenient(&content)?
+        } else {
+            (lean::from_file(path)?, Vec::new())
+        };
+        let mut text = Vec::new();
+        diagnostics.extend(self.convert_loaded_to_writer(&notebook, path, &mut text)?);
+        diagnostics.sort_by_key(|diagnostic| diagnostic.cell_index);
+        let mut text = String::from_utf8(text).expect("rendered text is valid UTF-8");
+        let appendix = self.convert_appendix(path, &notebook);
+        text.push_str(&appendix.text);
+        diagnostics.extend(appendix.diagnostics);
+        Ok((Conversion { text, diagnostics }, notebook.cells.len()))
+    }
+
+    /// Renders what follows `notebook`, loaded from `path`: the files it
+    /// refers to and previews of the data files it reads, as enabled.
+    pub fn convert_appendix<P: AsRef<Path>>(&self, path: P, notebook: &Notebook) -> Conversion {
+        let path = path.as_ref();
+        let mut appendix = self.convert_references(path, notebook);
+        let data_files = self.convert_data_files(path, notebook);
+        appendix.text.push_str(&data_files.text);
+        appendix.diagnostics.extend(data_files.diagnostics);
+        appendix
+    }
+
+    /// Renders the files `notebook`, loaded from `path`, refers to as an
+    /// appendix. Empty unless `with_references` was set.
+    pub fn convert_references<P: AsRef<Path>>(&self, path: P, notebook: &Notebook) -> Conversion {
+        let Some(limits) = self.references else {
+            return Conversion::default();
+        };
+        let resolution = resolve::resolve(self, path.as_ref(), notebook, limits);
+        Conversion {
+            text: resolution.to_string(),
+            diagnostics: resolution.diagnostics,
+        }
+    }
+
+    /// Renders previews of the data files `notebook`, loaded from `path`,
+    /// reads. Empty unless `with_data_files` was set.
+    pub fn convert_data_files<P: AsRef<Path>>(&self, path: P, notebook: &Notebook) -> Conversion {
+        let Some(limits) = self.data_files else {
+            return Conversion::default();
+        };
+        let previews = sniff::sniff(self, path.as_ref(), notebook, limits);
+        Conversion {
+            text: previews.to_string(),
+            diagnostics: previews.diagnostics,
+        }
+    }
+
+    /// Converts notebook JSON. Outside of recovery mode the notebook is
+    /// parsed into the borrowed model; see the `borrowed` module.
+    pub fn convert_str(&self, content: &str) -> Result<Conversion, JupyterError> {
+        self.convert_str_counted(content)
+            .map(|(conversion, _)| conversion)
+    }
+
+    /// Like `convert_str`, also returning the number of cells in the
+    /// notebook.
+    pub(crate) fn convert_str_counted(
+        &self,
+        content: &str,
+    ) -> Result<(Conversion, usize), JupyterError> {
+        if !self.recover {
+            let notebook = BorrowedNotebook::parse(content)?;
+            let mut text = Vec::new();
+            let mut diagnostics = self.convert_borrowed_to_writer(&notebook, &mut text)?;
+            diagnostics.sort_by_key(|diagnostic| diagnostic.cell_index);
+            let conversion = Conversion {
+                text: String::from_utf8(text).expect("rendered text is valid UTF-8"),
+                diagnostics,
+            };
+            return Ok((conversion, notebook.cells.len()));
+        }
+        let (notebook, mut diagnostics) = recovery::parse_lenient(content)?;
+        let mut conversion = self.convert_notebook(&notebook)?;
+        diagnostics.append(&mut conversion.diagnostics);
+        diagnostics.sort_by_key(|diagnostic| diagnostic.cell_index);
+        conversion.diagnostics = diagnostics;
+        Ok((conversion, notebook.cells.len()))
+    }
+
+    /// Renders the notebook into a `String`. A thin wrapper around
+    /// `convert_to_writer`.
+    pub fn convert_notebook(&self, notebook: &Notebook) -> Result<Conversion, JupyterError> {
+        let mut text = Vec::new();
+        let diagnostics = self.convert_to_writer(notebook, &mut text)?;
+        Ok(Conversion {
+            text: String::from_utf8(text).expect("rendered text is valid UTF-8"),
+            diagnostics,
+        })
+    }
+
+    /// Renders the notebook into `writer`, one cell at a time, and returns
+    /// the diagnostics collected on the way.
+    pub fn convert_to_writer<W: Write>(
+        &self,
+        notebook: &Notebook,
+        writer: W,
+    ) -> Result<Vec<Diagnostic>, JupyterError> {
+        self.write_notebook(notebook, None, writer)
+    }
+
+    /// Like `convert_to_writer`, for a notebook loaded from `path`. Imports
+    /// of modules beside the file are listed as local in the metadata.
+    pub fn convert_loaded_to_writer<W: Write>(
+        &self,
+        notebook: &Notebook,
+        path: &Path,
+        writer: W,
+    ) -> Result<Vec<Diagnostic>, JupyterError> {
+        let dir = path.parent().unwrap_or(Path::new(""));
+        self.write_notebook(notebook, Some(dir), writer)
+    }
+
+    fn write_notebook<W: Write>(
+        &self,
+        notebook: &Notebook,
+        dir: Option<&Path>,
+        writer: W,
+    ) -> Result<Vec<Diagnostic>, JupyterError> {
+        let mut diagnostics = Vec::new();
+        if self.redactor.is_none() && self.pii_scrubber.is_none() && self.anonymizer.is_none() {
+            self.render_notebook(notebook, dir, writer, &mut diagnostics)?;
+            return Ok(diagnostics);
         }
 
-        // Process each cell
+        let mut prepared = notebook.clone();
+        if let Some(redactor) = &self.redactor {
+            let report = redactor.redact_notebook(&mut prepared);
+            diagnostics.extend(report.redactions.iter().map(|redaction| {
+                Diagnostic::note(
+                    redaction.cell_index,
+                    "redacted",
+                    format!(
+                        "replaced {} {} match(es) in the {}",
+                        redaction.count, redaction.rule, redaction.location
+                    ),
+                )
+            }));
+        }
+        if let Some(scrubber) = &self.pii_scrubber {
+            let report = scrubber.scrub_notebook(&mut prepared);
+            diagnostics.extend(report.findings.iter().map(|finding| {
+                Diagnostic::note(
+                    Some(finding.cell_index),
+                    "scrubbed",
+                    format!(
+                        "replaced {} {} value(s) in the outputs",
+                        finding.count, finding.kind
+                    ),
+                )
+            }));
+        }
+        if let Some(anonymizer) = &self.anonymizer {
+            anonymizer.anonymize_notebook(&mut prepared);
+        }
+        self.render_notebook(&prepared, dir, writer, &mut diagnostics)?;
+        Ok(diagnostics)
+    }
+
+    /// Redacts and anonymizes the source of a file shown next to the
+    /// notebook, such as a referenced module, and returns the number of
+    /// matches per redaction rule.
+    pub(crate) fn filter_source(&self, source: &mut String) -> Vec<(String, usize)> {
+        let mut redactions = Vec::new();
+        if let Some(redactor) = &self.redactor {
+            redactions = redactor.redact_text(source);
+        }
+        if let Some(anonymizer) = &self.anonymizer {
+            *source = anonymizer.anonymize_text(source);
+        }
+        redactions
+    }
+
+    /// Renders a borrowed notebook into `writer`, like `convert_to_writer`.
+    ///
+    /// Cells and outputs are rendered straight from the borrowed model.
+    /// Options that analyze or rewrite the whole notebook (metadata, outline,
+    /// sections, context, errors-only, execution order, redaction, scrubbing
+    /// and anonymization) work on an owned copy instead.
+    pub fn convert_borrowed_to_writer<W: Write>(
+        &self,
+        notebook: &BorrowedNotebook,
+        mut writer: W,
+    ) -> Result<Vec<Diagnostic>, JupyterError> {
+        if self.needs_owned_model() {
+            return self.convert_to_writer(&notebook.to_notebook()?, writer);
+        }
+
+        let mut diagnostics = Vec::new();
         for (index, cell) in notebook.cells.iter().enumerate() {
-            output.push_str(&self.format_cell(cell, index + 1)?);
-            output.push_str("\n\n");
+            let cell_str = self.format_cell(cell, index, self.include_outputs, &mut diagnostics)?;
+            write!(writer, "{}\n\n", cell_str).map_err(JupyterError::WriteError)?;
         }
+        Ok(diagnostics)
+    }
 
-        Ok(output)
+    fn needs_owned_model(&self) -> bool {
+        self.include_metadata
+            || self.include_outline
+            || self.errors_only
+            || self.section.is_some()
+            || self.context_for.is_some()
+            || self.cell_order != CellOrder::default()
+            || self.redactor.is_some()
+            || self.pii_scrubber.is_some()
+            || self.anonymizer.is_some()
     }
 
-    fn format_metadata(&self, notebook: &Notebook) -> String {
+    /// `dir` is the directory of the notebook file, when known.
+    fn render_notebook<W: Write>(
+        &self,
+        notebook: &Notebook,
+        dir: Option<&Path>,
+        mut writer: W,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<(), JupyterError> {
+        let selected = self.selected_cells(notebook)?;
+
+        // Add notebook metadata and outline if requested
+        if self.include_metadata || self.include_outline || self.errors_only {
+            let metadata = self.format_metadata(notebook, dir, &selected, diagnostics);
+            write!(writer, "{}\n\n", metadata).map_err(JupyterError::WriteError)?;
+        }
+
+        // Process each selected cell, keeping its original number
+        for index in selected {
+            let cell = &notebook.cells[index];
+            let show_outputs = self.include_outputs || (self.errors_only && cell.has_error());
+            let cell_str = self.format_cell(cell, index, show_outputs, diagnostics)?;
+            write!(writer, "{}\n\n", cell_str).map_err(JupyterError::WriteError)?;
+        }
+
+        Ok(())
+    }
+
+    fn selected_cells(&self, notebook: &Notebook) -> Result<Vec<usize>, JupyterError> {
+        let mut selected: Vec<usize> = (0..notebook.cells.len()).collect();
+
+        if let Some(path) = &self.section {
+            let sections = sections::sections(notebook);
+            let section = sections::find_section(&sections, path)
+                .ok_or_else(|| JupyterError::SectionNotFound(path.clone()))?;
+            selected.retain(|&index| section.contains(index));
+        }
+
+        if let Some(cell_number) = self.context_for {
+            let target = cell_number
+                .checked_sub(1)
+                .filter(|&index| {
+                    notebook
+                        .cells
+                        .get(index)
+                        .is_some_and(|cell| cell.cell_type == "code")
+                })
+                .ok_or(JupyterError::CellNotFound(cell_number))?;
+            let mut context = DependencyGraph::build(notebook).closure(target);
+            for index in context.clone() {
+                context.extend(
+                    (0..index)
+                        .rev()
+                        .take_while(|&previous| notebook.cells[previous].cell_type == "markdown"),
+                );
+            }
+            selected.retain(|index| context.contains(index));
+        }
+
+        if self.errors_only {
+            let context = self.error_context.unwrap_or(DEFAULT_ERROR_CONTEXT);
+            let mut kept = std::collections::BTreeSet::new();
+            for (position, &index) in selected.iter().enumerate() {
+                if !notebook.cells[index].has_error() {
+                    continue;
+                }
+                kept.insert(index);
+                kept.extend(
+                    selected[..position]
+                        .iter()
+                        .rev()
+                        .filter(|&&previous| notebook.cells[previous].cell_type == "code")
+                        .take(context),
+                );
+            }
+            selected = kept.into_iter().collect();
+        }
+
+        if self.cell_order == CellOrder::Execution {
+            let order = ExecutionAnalysis::execution_order(notebook);
+            let mut rank = vec![0; order.len()];
+            for (position, &index) in order.iter().enumerate() {
+                rank[index] = position;
+            }
+            selected.sort_by_key(|&index| rank[index]);
+        }
+
+        Ok(selected)
+    }
+
+    fn format_metadata(
+        &self,
+        notebook: &Notebook,
+        dir: Option<&Path>,
+        selected: &[usize],
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> String {
         let mut metadata_str = String::from("# Jupyter Notebook\n\n");
+        let mut blocks = Vec::new();
 
+        if self.errors_only {
+            blocks.push(self.format_failures(notebook, selected));
+        }
+
+        if self.include_metadata || self.errors_only {
+            let mut info = String::new();
+            self.format_notebook_info(notebook, &mut info);
+            blocks.push(info);
+        }
+
+        if self.include_metadata {
+            let mut environment = Environment::scan(notebook);
+            if let Some(dir) = dir {
+                environment = environment.with_local_modules(dir);
+            }
+            if !environment.is_empty() {
+                blocks.push(environment.to_string());
+            }
+        }
+
+        if self.include_outline {
+            let (outline, cut) = outline::render_outline_with_cuts(notebook);
+            diagnostics.extend(cut.into_iter().map(|index| {
+                Diagnostic::note(
+                    Some(index),
+                    "truncated",
+                    "shortened the summary of the cell in the outline",
+                )
+            }));
+            blocks.push(outline);
+        }
+
+        metadata_str.push_str(&blocks.join("\n"));
+        metadata_str
+    }
+
+    fn format_failures(&self, notebook: &Notebook, selected: &[usize]) -> String {
+        let failures: Vec<String> = selected
+            .iter()
+            .flat_map(|&index| {
+                notebook.cells[index]
+                    .outputs
+                    .iter()
+                    .flatten()
+                    .filter(|output| output.output_type == "error")
+                    .map(move |output| {
+                        format!("Cell {}: {}", index + 1, traceback::summary(output))
+                    })
+            })
+            .collect();
+
+        if failures.is_empty() {
+            "**Failures**: none\n".to_string()
+        } else {
+            format!(
+                "**Failures** ({}): {}\n",
+                failures.len(),
+                failures.join(" | ")
+            )
+        }
+    }
+
+    fn format_notebook_info(&self, notebook: &Notebook, metadata_str: &mut String) {
         if let Some(kernelspec) = notebook.metadata.get("kernelspec") {
             if let Some(name) = kernelspec.get("name").and_then(|v| v.as_str()) {
                 metadata_str.push_str(&format!("**Kernel**: {}\n", name));
@@ -127,17 +829,26 @@ impl JupyterConverter {
         ));
         metadata_str.push_str(&format!("**Total Cells**: {}\n", notebook.cells.len()));
 
-        metadata_str
+        if notebook.cells.iter().any(|cell| cell.cell_type == "code") {
+            metadata_str.push_str(&ExecutionAnalysis::analyze(notebook).to_string());
+        }
     }
 
-    fn format_cell(&self, cell: &Cell, cell_number: usize) -> Result<String, JupyterError> {
+    fn format_cell<C: CellView>(
+        &self,
+        cell: &C,
+        index: usize,
+        show_outputs: bool,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<String, JupyterError> {
         let mut cell_str = String::new();
+        let cell_number = index + 1;
 
-        match cell.cell_type.as_str() {
+        match cell.cell_type() {
             "markdown" => {
                 cell_str.push_str(&format!("## Cell {}: Markdown\n", cell_number));
                 cell_str.push_str("```markdown\n");
-                for line in &cell.source {
+                for line in cell.source() {
                     cell_str.push_str(line);
                     cell_str.push('\n');
                 }
@@ -147,53 +858,86 @@ impl JupyterConverter {
                 cell_str.push_str(&format!("## Cell {}: Code\n", cell_number));
 
                 // Add execution count if present
-                if let Some(exec_count) = cell.execution_count {
+                if let Some(exec_count) = cell.execution_count() {
                     cell_str.push_str(&format!("*Execution Count: {}*\n", exec_count));
                 }
 
                 // Add source code
                 cell_str.push_str("```python\n");
-                for line in &cell.source {
+                for line in cell.source() {
                     cell_str.push_str(line);
                     cell_str.push('\n');
                 }
                 cell_str.push_str("```\n");
 
                 // Add outputs if requested and present
-                if self.include_outputs {
-                    if let Some(outputs) = &cell.outputs {
-                        if !outputs.is_empty() {
-                            cell_str.push_str("### Outputs\n");
-                            for output in outputs {
-                                cell_str.push_str(&self.format_output(output));
-                            }
-                        }
+                if show_outputs && !cell.outputs().is_empty() {
+                    cell_str.push_str("### Outputs\n");
+                    for output in cell.outputs() {
+                        cell_str.push_str(&self.format_output(output, index, diagnostics));
                     }
                 }
             }
             "raw" => {
                 cell_str.push_str(&format!("## Cell {}: Raw\n", cell_number));
                 cell_str.push_str("```\n");
-                for line in &cell.source {
+                for line in cell.source() {
+                    cell_str.push_str(line);
+                    cell_str.push('\n');
+                }
+                cell_str.push_str("```\n");
+            }
+            recovery::UNREADABLE_CELL_TYPE if self.recover => {
+                cell_str.push_str(&format!("## Cell {}: Unreadable\n", cell_number));
+                cell_str.push_str(&format!(
+                    "*Placeholder: the cell could not be read ({})*\n",
+                    cell.source().collect::<Vec<_>>().join(" ")
+                ));
+            }
+            _ if self.recover => {
+                diagnostics.push(Diagnostic::warning(
+                    Some(index),
+                    "unknown-cell-type",
+                    format!(
+                        "unknown cell type '{}', rendered as a placeholder",
+                        cell.cell_type()
+                    ),
+                ));
+                cell_str.push_str(&format!(
+                    "## Cell {}: Unknown ({})\n",
+                    cell_number,
+                    cell.cell_type()
+                ));
+                cell_str.push_str("*Placeholder: unknown cell type, source shown as plain text*\n");
+                cell_str.push_str("```\n");
+                for line in cell.source() {
                     cell_str.push_str(line);
                     cell_str.push('\n');
                 }
                 cell_str.push_str("```\n");
             }
             _ => {
-                return Err(JupyterError::InvalidCellType(cell.cell_type.clone()));
+                return Err(JupyterError::InvalidCellType {
+                    cell_type: cell.cell_type().to_string(),
+                    location: Some(Box::new(ErrorLocation::in_cell(index, "cell_type"))),
+                });
             }
         }
 
         Ok(cell_str)
     }
 
-    fn format_output(&self, output: &Output) -> String {
+    fn format_output<O: OutputView>(
+        &self,
+        output: &O,
+        index: usize,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> String {
         let mut output_str = String::new();
 
-        match output.output_type.as_str() {
+        match output.output_type() {
             "stream" => {
-                if let Some(text) = &output.text {
+                if let Some(text) = output.text() {
                     output_str.push_str("**Stream Output**:\n");
                     output_str.push_str("```\n");
                     for line in text {
@@ -204,46 +948,81 @@ impl JupyterConverter {
                 }
             }
             "execute_result" | "display_data" => {
-                if let Some(data) = &output.data {
-                    if let Some(text_plain) = data.get("text/plain") {
-                        if let Some(text) = text_plain.as_str() {
-                            output_str.push_str("**Result**:\n");
-                            output_str.push_str("```\n");
-                            output_str.push_str(text);
-                            output_str.push_str("\n```\n");
-                        } else if let Some(text_array) = text_plain.as_array() {
-                            output_str.push_str("**Result**:\n");
-                            output_str.push_str("```\n");
-                            for item in text_array {
-                                if let Some(s) = item.as_str() {
-                                    output_str.push_str(s);
-                                    output_str.push('\n');
-                                }
-                            }
-                            output_str.push_str("```\n");
+                if let Some(mime_types) = output.mime_types() {
+                    for mime_type in &mime_types {
+                        if !KNOWN_MIME_TYPES.contains(mime_type) {
+                            diagnostics.push(Diagnostic::note(
+                                Some(index),
+                                "unknown-mime-type",
+                                format!("skipped data of unknown MIME type {}", mime_type),
+                            ));
                         }
                     }
+                    if !mime_types.contains(&"text/plain") && !mime_types.is_empty() {
+                        diagnostics.push(Diagnostic::warning(
+                            Some(index),
+                            "dropped-output",
+                            format!(
+                                "skipped an output without a text/plain form ({})",
+                                mime_types.join(", ")
+                            ),
+                        ));
+                    }
+                    if let Some(text) = output.plain_text() {
+                        output_str.push_str("**Result**:\n");
+                        output_str.push_str("```\n");
+                        for line in text {
+                            output_str.push_str(&line);
+                            output_str.push('\n');
+                        }
+                        output_str.push_str("```\n");
+                    }
                 }
             }
             "error" => {
-                if let Some(data) = &output.data {
-                    if let Some(traceback) = data.get("traceback") {
-                        output_str.push_str("**Error**:\n");
-                        output_str.push_str("```\n");
-                        if let Some(tb_array) = traceback.as_array() {
-                            for item in tb_array {
-                                if let Some(s) = item.as_str() {
-                                    output_str.push_str(s);
-                                    output_str.push('\n');
-                                }
-                            }
+                if output.has_nested_traceback() {
+                    diagnostics.push(Diagnostic::note(
+                        Some(index),
+                        "upgrade",
+                        "read the traceback stored under data",
+                    ));
+                }
+                let traceback = match self.traceback_mode {
+                    TracebackMode::Full => output
+                        .traceback_lines()
+                        .map(|lines| lines.into_iter().map(Cow::into_owned).collect()),
+                    TracebackMode::Compact => {
+                        let (lines, omitted) = traceback::compact_counted(&output.as_output());
+                        if omitted > 0 {
+                            diagnostics.push(Diagnostic::note(
+                                Some(index),
+                                "truncated",
+                                format!("omitted {} library frame(s) from the traceback", omitted),
+                            ));
                         }
-                        output_str.push_str("```\n");
+                        Some(lines)
+                    }
+                };
+                if let Some(traceback) = traceback {
+                    output_str.push_str("**Error**:\n");
+                    output_str.push_str("```\n");
+                    for line in traceback {
+                        output_str.push_str(&line);
+                        output_str.push('\n');
                     }
+                    output_str.push_str("```\n");
                 }
             }
             _ => {
-                output_str.push_str(&format!("**Output Type: {}**\n", output.output_type));
+                diagnostics.push(Diagnostic::warning(
+                    Some(index),
+                    "unknown-output-type",
+                    format!(
+                        "unknown output type '{}', only its type is shown",
+                        output.output_type()
+                    ),
+                ));
+                output_str.push_str(&format!("**Output Type: {}**\n", output.output_type()));
             }
         }
 
@@ -255,6 +1034,79 @@ impl JupyterConverter {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_error_names_the_cause() {
+        // The cell id is a strict issue for nbformat 4.4 but does not stop
+        // the parse; the output text does.
+        let content = r#"{"cells": [{"id": "a1", "cell_type": "code", "metadata": {},
+            "source": [], "execution_count": null,
+            "outputs": [{"output_type": "stream", "name": "stdout", "text": 5}]}],
+            "metadata": {}, "nbformat": 4, "nbformat_minor": 4}"#;
+        let err = content.parse::<Notebook>().unwrap_err();
+        assert_eq!(
+            err.location()
+                .and_then(|location| location.pointer.as_deref()),
+            Some("/cells/0/outputs/0/text")
+        );
+        assert!(err.to_string().contains("text must be a string"));
+
+        let missing =
+            content
+                .replace(r#""text": 5"#, r#""text": []"#)
+                .replacen(r#""metadata": {},"#, "", 1);
+        let err = missing.parse::<Notebook>().unwrap_err();
+        assert_eq!(
+            err.location()
+                .and_then(|location| location.pointer.as_deref()),
+            Some("/cells/0")
+        );
+        assert!(err.to_string().contains("missing required key 'metadata'"));
+    }
+
+    #[test]
+    fn test_parse_error_points_at_key() {
+        let content = r##"{
+ "cells": [
+  {"cell_type": "markdown", "metadata": {}, "text": ["# Title"]}
+ ],
+ "metadata": {}, "nbformat": 4, "nbformat_minor": 4
+}"##;
+        let error = content.parse::<Notebook>().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid notebook format: missing required key 'source', in /cells/0 (cell 1)"
+        );
+        let location = error.location().unwrap();
+        assert_eq!(location.cell_index, Some(0));
+        assert_eq!((location.line, location.column), (Some(3), Some(3)));
+    }
+
+    #[test]
+    fn test_json_error_location() {
+        let content = r##"{
+ "cells": [
+  {"cell_type": "markdown", "metadata": {}, "source": ["# Title"]},
+  {"cell_type": "code", "execution_count": 1, "metadata": {}, "outputs": [],
+   "source": [
+    "a" "b"
+   ]}
+ ],
+ "metadata": {}, "nbformat": 4, "nbformat_minor": 4
+}"##;
+        let error = content.parse::<Notebook>().unwrap_err();
+        assert!(matches!(error, JupyterError::JsonParseError { .. }));
+        assert!(error
+            .to_string()
+            .ends_with(", in /cells/1/source/0 (cell 2)"));
+
+        let location = error.location().unwrap();
+        assert_eq!(location.cell_index, Some(1));
+        assert_eq!(location.line, Some(6));
+        let snippet = location.snippet.as_ref().unwrap();
+        assert_eq!(snippet.text, r#"    "a" "b""#);
+        assert_eq!(&snippet.text[snippet.caret..], r#""b""#);
+    }
+
     #[test]
     fn test_converter_creation() {
         let converter = JupyterConverter::new();
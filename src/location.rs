@@ -0,0 +1,340 @@
+//! Locations of errors inside the notebook JSON.
+//!
+//! serde only reports a line and a column, which says little in a 10 MB
+//! notebook stored on one line. This module maps a position to the JSON
+//! pointer of the value being read, and back, by scanning the raw text. The
+//! scan is tolerant of invalid and truncated JSON, since that is exactly
+//! what it is used on.
+
+use crate::JupyterError;
+use std::fmt;
+
+/// Characters of context kept on each side of the offending position.
+const SNIPPET_CONTEXT: usize = 40;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorLocation {
+    /// Zero-based index of the cell being processed.
+    pub cell_index: Option<usize>,
+    /// JSON pointer to the offending value, e.g. `/cells/12/source/3`.
+    pub pointer: Option<String>,
+    /// 1-based line and column in the notebook file.
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub snippet: Option<Snippet>,
+}
+
+/// An excerpt of the line around the offending position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    pub text: String,
+    /// Character offset of the offending position within `text`.
+    pub caret: usize,
+}
+
+impl ErrorLocation {
+    /// Locates a position given by serde's 1-based line and column.
+    pub fn at_line_column(content: &str, line: usize, column: usize) -> Self {
+        let offset = offset_of_line_column(content, line, column);
+        let pointer = pointer_at(content, offset);
+        Self {
+            cell_index: cell_index(&pointer),
+            pointer: Some(pointer),
+            line: Some(line),
+            column: Some(column),
+            snippet: Some(snippet(content, offset)),
+        }
+    }
+
+    /// Locates the value at a JSON pointer. Line, column and snippet are
+    /// filled in when the value can be found in the text.
+    pub fn at_pointer(content: &str, pointer: &str) -> Self {
+        let mut location = Self {
+            cell_index: cell_index(pointer),
+            pointer: Some(pointer.to_string()),
+            ..Self::default()
+        };
+        if let Some(offset) = offset_of_pointer(content, pointer) {
+            let (line, column) = line_column(content, offset);
+            location.line = Some(line);
+            location.column = Some(column);
+            location.snippet = Some(snippet(content, offset));
+        }
+        location
+    }
+
+    /// A location known only by its cell.
+    pub fn in_cell(cell_index: usize, key: &str) -> Self {
+        Self {
+            cell_index: Some(cell_index),
+            pointer: Some(format!("/cells/{}/{}", cell_index, key)),
+            ..Self::default()
+        }
+    }
+}
+
+impl fmt::Display for ErrorLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.pointer, self.cell_index) {
+            (Some(pointer), Some(index)) => write!(f, "{} (cell {})", pointer, index + 1),
+            (Some(pointer), None) => write!(f, "{}", pointer),
+            (None, Some(index)) => write!(f, "cell {}", index + 1),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// Renders the location as a suffix of an error message.
+pub(crate) fn describe(location: &Option<Box<ErrorLocation>>) -> String {
+    match location {
+        // The empty pointer names the whole document.
+        Some(location) if location.pointer.as_deref() == Some("") => {
+            ", at the top level".to_string()
+        }
+        Some(location) if location.pointer.is_some() || location.cell_index.is_some() => {
+            format!(", in {}", location)
+        }
+        _ => String::new(),
+    }
+}
+
+/// Wraps a JSON syntax error with the location it points at.
+pub(crate) fn parse_error(err: serde_json::Error, content: &str) -> JupyterError {
+    let location = (err.line() > 0).then(|| {
+        Box::new(ErrorLocation::at_line_column(
+            content,
+            err.line(),
+            err.column(),
+        ))
+    });
+    JupyterError::JsonParseError {
+        source: err,
+        location,
+    }
+}
+
+fn cell_index(pointer: &str) -> Option<usize> {
+    let mut tokens = pointer.split('/').skip(1);
+    match (tokens.next(), tokens.next()) {
+        (Some("cells"), Some(index)) => index.parse().ok(),
+        _ => None,
+    }
+}
+
+fn offset_of_line_column(content: &str, line: usize, column: usize) -> usize {
+    let line_start = if line <= 1 {
+        0
+    } else {
+        content
+            .match_indices('\n')
+            .nth(line - 2)
+            .map_or(content.len(), |(position, _)| position + 1)
+    };
+    // serde counts columns in bytes; column 0 means "before the line".
+    let offset = (line_start + column.saturating_sub(1)).min(content.len());
+    floor_char_boundary(content, offset)
+}
+
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |position| position + 1);
+    (line, offset - line_start + 1)
+}
+
+fn floor_char_boundary(content: &str, mut offset: usize) -> usize {
+    while !content.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+fn snippet(content: &str, offset: usize) -> Snippet {
+    let line_start = content[..offset]
+        .rfind('\n')
+        .map_or(0, |position| position + 1);
+    let line_end = content[offset..]
+        .find('\n')
+        .map_or(content.len(), |position| offset + position);
+
+    let before: Vec<char> = content[line_start..offset].chars().collect();
+    let after: Vec<char> = content[offset..line_end].chars().collect();
+    let mut text = String::new();
+    let skipped = before.len().saturating_sub(SNIPPET_CONTEXT);
+    if skipped > 0 {
+        text.push_str("...");
+    }
+    text.extend(&before[skipped..]);
+    let caret = text.chars().count();
+    text.extend(after.iter().take(SNIPPET_CONTEXT));
+    if after.len() > SNIPPET_CONTEXT {
+        text.push_str("...");
+    }
+
+    Snippet {
+        text: text.replace('\t', " ").trim_end_matches('\r').to_string(),
+        caret,
+    }
+}
+
+enum Frame {
+    Object { key: Option<String> },
+    Array { index: usize },
+}
+
+/// Walks the JSON text, calling `visit` with the offset and pointer of the
+/// start of every value until it returns false. Returns the open frames at
+/// the point where the walk stopped.
+fn walk(content: &str, mut visit: impl FnMut(usize, &[Frame]) -> bool) -> Vec<Frame> {
+    let bytes = content.as_bytes();
+    let mut frames: Vec<Frame> = Vec::new();
+    let mut expecting_key = false;
+    let mut position = 0;
+
+    while position < bytes.len() {
+        let byte = bytes[position];
+        match byte {
+            b'"' => {
+                let end = string_end(bytes, position);
+                if expecting_key {
+                    let key = serde_json::from_str(&content[position..end]).unwrap_or_else(|_| {
+                        content[position + 1..end.max(position + 1)].to_string()
+                    });
+                    if let Some(Frame::Object { key: slot }) = frames.last_mut() {
+                        *slot = Some(key);
+                    }
+                    expecting_key = false;
+                } else if !visit(position, &frames) {
+                    return frames;
+                }
+                position = end;
+                continue;
+            }
+            b'{' | b'[' => {
+                if !visit(position, &frames) {
+                    return frames;
+                }
+                if byte == b'{' {
+                    frames.push(Frame::Object { key: None });
+                    expecting_key = true;
+                } else {
+                    frames.push(Frame::Array { index: 0 });
+                }
+            }
+            b'}' | b']' => {
+                frames.pop();
+                expecting_key = false;
+            }
+            b',' => match frames.last_mut() {
+                Some(Frame::Object { key }) => {
+                    *key = None;
+                    expecting_key = true;
+                }
+                Some(Frame::Array { index }) => *index += 1,
+                None => {}
+            },
+            b':' => {}
+            byte if byte.is_ascii_whitespace() => {}
+            _ => {
+                // A number or a literal.
+                if !visit(position, &frames) {
+                    return frames;
+                }
+                while position < bytes.len()
+                    && !matches!(bytes[position], b',' | b'}' | b']' | b'"')
+                    && !bytes[position].is_ascii_whitespace()
+                {
+                    position += 1;
+                }
+                continue;
+            }
+        }
+        position += 1;
+    }
+    frames
+}
+
+/// Returns the offset just past the string starting at `start`.
+fn string_end(bytes: &[u8], start: usize) -> usize {
+    let mut position = start + 1;
+    while position < bytes.len() {
+        match bytes[position] {
+            b'\\' => position += 2,
+            b'"' => return position + 1,
+            _ => position += 1,
+        }
+    }
+    bytes.len()
+}
+
+fn pointer_of(frames: &[Frame]) -> String {
+    let mut pointer = String::new();
+    for frame in frames {
+        match frame {
+            Frame::Object { key: Some(key) } => {
+                pointer.push('/');
+                pointer.push_str(&key.replace('~', "~0").replace('/', "~1"));
+            }
+            Frame::Object { key: None } => {}
+            Frame::Array { index } => pointer.push_str(&format!("/{}", index)),
+        }
+    }
+    pointer
+}
+
+/// JSON pointer of the innermost value being read at `offset`.
+fn pointer_at(content: &str, offset: usize) -> String {
+    let frames = walk(&content[..offset], |_, _| true);
+    pointer_of(&frames)
+}
+
+fn offset_of_pointer(content: &str, pointer: &str) -> Option<usize> {
+    let mut found = None;
+    walk(content, |position, frames| {
+        if pointer_of(frames) == pointer {
+            found = Some(position);
+            return false;
+        }
+        true
+    });
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pointer_round_trip() {
+        let content = r#"{"cells": [{"source": ["a"]}, {"source": ["b", "c"], "x/y": 1}]}"#;
+        let offset = content.find("\"c\"").unwrap();
+        assert_eq!(pointer_at(content, offset + 1), "/cells/1/source/1");
+        assert_eq!(
+            offset_of_pointer(content, "/cells/1/source/1"),
+            Some(offset)
+        );
+        assert_eq!(
+            offset_of_pointer(content, "/cells/1/x~1y"),
+            content.find('1')
+        );
+        assert_eq!(offset_of_pointer(content, "/cells/2"), None);
+    }
+
+    #[test]
+    fn test_describe_top_level() {
+        let content = r#"{"cells": []}"#;
+        let location = |pointer: &str| Some(Box::new(ErrorLocation::at_pointer(content, pointer)));
+        assert_eq!(describe(&location("")), ", at the top level");
+        assert_eq!(describe(&location("/cells")), ", in /cells");
+    }
+
+    #[test]
+    fn test_snippet_window() {
+        let content = format!("{{\"cells\": [{}\"bad\" \"x\"]}}", "1, ".repeat(30));
+        let offset = content.find("\"x\"").unwrap();
+        let snippet = snippet(&content, offset);
+        assert!(snippet.text.starts_with("..."));
+        assert_eq!(snippet.text.chars().nth(snippet.caret), Some('"'));
+        assert!(snippet.text[snippet.caret..].starts_with("\"x\"]}"));
+    }
+}
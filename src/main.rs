@@ -1,25 +1,641 @@
+use clap::ArgMatches;
 use clap::{Arg, Command};
-use jupyter2llm::JupyterConverter;
-use std::path::PathBuf;
+use jupyter2llm::anonymize::Anonymizer;
+use jupyter2llm::batch::{self, BatchOutput, BatchRunner};
+use jupyter2llm::bundle::{self, Bundler, EntryStatus};
+use jupyter2llm::deps::DependencyGraph;
+use jupyter2llm::diagnostic::Diagnostic;
+use jupyter2llm::environment::Environment;
+use jupyter2llm::execution::CellOrder;
+use jupyter2llm::lean;
+use jupyter2llm::lint::{self, Linter, Severity};
+use jupyter2llm::pii::PiiScrubber;
+use jupyter2llm::recovery;
+use jupyter2llm::redact::Redactor;
+use jupyter2llm::resolve::ReferenceLimits;
+use jupyter2llm::script;
+use jupyter2llm::sniff::DataLimits;
+use jupyter2llm::traceback::TracebackMode;
+use jupyter2llm::validate::{ValidationLevel, Validator};
+use jupyter2llm::{sections, JupyterConverter, JupyterError, Notebook, DEFAULT_ERROR_CONTEXT};
+use std::fs::File;
+use std::io::{BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// Exits with an error unless the path names an existing `.ipynb` file.
+fn validate_input(input_path: &str) -> PathBuf {
+    let input_path = PathBuf::from(input_path);
+    if !input_path.exists() {
+        eprintln!(
+            "Error: Input file '{}' does not exist",
+            input_path.display()
+        );
+        std::process::exit(1);
+    }
+
+    if input_path.extension().is_none_or(|ext| ext != "ipynb") {
+        eprintln!("Error: Input file must have .ipynb extension");
+        std::process::exit(1);
+    }
+
+    input_path
+}
+
+/// Reads the notebook, leniently in recovery mode, with the diagnostics of
+/// what had to be repaired.
+fn load_notebook(
+    input_path: &Path,
+    recover: bool,
+) -> Result<(Notebook, Vec<Diagnostic>), Box<dyn std::error::Error>> {
+    if !recover {
+        return Ok((lean::from_file(input_path)?, Vec::new()));
+    }
+    let content = std::fs::read_to_string(input_path)?;
+    Ok(recovery::parse_lenient(&content)?)
+}
+
+/// Prints the diagnostics of a conversion in cell order, unless quiet.
+fn report_diagnostics(diagnostics: &mut [Diagnostic], quiet: bool) {
+    if quiet {
+        return;
+    }
+    diagnostics.sort_by_key(|diagnostic| diagnostic.cell_index);
+    for diagnostic in diagnostics.iter() {
+        eprintln!("{}", diagnostic);
+    }
+}
+
+/// A batch runner on `jobs` threads that keeps a progress line updated
+/// while stderr is a terminal.
+fn batch_runner(converter: &JupyterConverter, jobs: usize, quiet: bool) -> BatchRunner<'_> {
+    let runner = BatchRunner::new(converter).with_jobs(jobs);
+    if quiet || !std::io::stderr().is_terminal() {
+        return runner;
+    }
+    runner.with_progress(|progress| {
+        eprint!(
+            "\rConverted {}/{} notebook(s), {} failed",
+            progress.done, progress.total, progress.failed
+        );
+        if progress.done == progress.total {
+            eprintln!();
+        }
+    })
+}
+
+/// Prints the diagnostics of each notebook and the summary table of a
+/// batch, and exits with an error if any notebook failed. Failures are
+/// printed even when quiet.
+fn report_batch(mut report: batch::BatchReport, quiet: bool) {
+    for file in &mut report.files {
+        match &mut file.result {
+            Ok(diagnostics) if !quiet => {
+                diagnostics.sort_by_key(|diagnostic| diagnostic.cell_index);
+                for diagnostic in diagnostics.iter() {
+                    eprintln!("{}: {}", file.path.display(), diagnostic);
+                }
+            }
+            Ok(_) => {}
+            Err(err) if quiet => eprintln!("Error: {}: {}", file.path.display(), err),
+            Err(_) => {}
+        }
+    }
+    if !quiet {
+        eprintln!("{}", report);
+    }
+    if report.failed() > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// Prints the diagnostics and skipped files of a bundle and its size, and
+/// exits with an error if a file failed. Failures are printed even when
+/// quiet.
+fn report_bundle(mut entries: Vec<bundle::BundleEntry>, quiet: bool) {
+    let mut failed = false;
+    for entry in &mut entries {
+        match &entry.status {
+            EntryStatus::Failed(err) => {
+                failed = true;
+                eprintln!("Error: {}: {}", entry.path.display(), err);
+            }
+            EntryStatus::Skipped(reason) if !quiet => {
+                eprintln!("Skipped {}: {}", entry.path.display(), reason);
+            }
+            _ => {}
+        }
+        if !quiet {
+            entry
+                .diagnostics
+                .sort_by_key(|diagnostic| diagnostic.cell_index);
+            for diagnostic in &entry.diagnostics {
+                eprintln!("{}: {}", entry.path.display(), diagnostic);
+            }
+        }
+    }
+    if !quiet {
+        let included: Vec<_> = entries.iter().filter(|entry| entry.is_included()).collect();
+        eprintln!(
+            "Bundled {} file(s), ~{} tokens",
+            included.len(),
+            included.iter().map(|entry| entry.tokens).sum::<usize>()
+        );
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+/// Appends the files the notebook refers to and previews of its data files,
+/// as enabled.
+fn write_appendix<W: Write>(
+    converter: &JupyterConverter,
+    input_path: &Path,
+    notebook: &Notebook,
+    mut writer: W,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<(), JupyterError> {
+    let appendix = converter.convert_appendix(input_path, notebook);
+    writer
+        .write_all(appendix.text.as_bytes())
+        .map_err(JupyterError::WriteError)?;
+    diagnostics.extend(appendix.diagnostics);
+    Ok(())
+}
+
+/// Writes a subcommand result to the output file, or to stdout.
+fn write_output(result: &str, output_path: Option<&String>, quiet: bool) -> std::io::Result<()> {
+    match output_path {
+        Some(output_path) => {
+            std::fs::write(output_path, result)?;
+            if !quiet {
+                eprintln!("Output written to: {}", output_path);
+            }
+        }
+        None => print!("{}", result),
+    }
+    Ok(())
+}
+
+fn run_script(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let input_path = validate_input(matches.get_one::<String>("input").unwrap());
+    let notebook = Notebook::from_file(&input_path)?;
+    let export = script::export_script(&notebook);
+
+    write_output(
+        &export.script,
+        matches.get_one::<String>("output"),
+        matches.get_flag("quiet"),
+    )?;
+    Ok(())
+}
+
+fn run_requirements(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let input_path = validate_input(matches.get_one::<String>("input").unwrap());
+    let notebook = Notebook::from_file(&input_path)?;
+    let dir = input_path.parent().unwrap_or(Path::new("."));
+    let environment = Environment::scan(&notebook).with_local_modules(dir);
+
+    write_output(
+        &environment.requirements(),
+        matches.get_one::<String>("output"),
+        matches.get_flag("quiet"),
+    )?;
+    Ok(())
+}
+
+fn run_lint(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    if matches.get_flag("list-rules") {
+        for rule in lint::RULES {
+            println!(
+                "{:<20} {:<8} {}",
+                rule.id, rule.default_severity, rule.description
+            );
+        }
+        return Ok(());
+    }
+
+    let input_path = validate_input(matches.get_one::<String>("input").unwrap());
+    let notebook = Notebook::from_file(&input_path)?;
+    let quiet = matches.get_flag("quiet");
+
+    let mut linter = Linter::new();
+    for setting in matches.get_many::<String>("rule").unwrap_or_default() {
+        let Some((rule, level)) = setting.split_once('=') else {
+            eprintln!(
+                "Error: Rule setting '{}' must have the form ID=LEVEL",
+                setting
+            );
+            std::process::exit(1);
+        };
+        linter = linter.with_severity(rule, level.parse()?)?;
+    }
+    let report = linter.lint(&notebook);
+
+    let result = match matches.get_one::<String>("format").map(String::as_str) {
+        Some("json") => format!("{}\n", serde_json::to_string_pretty(&report.to_json())?),
+        Some("sarif") => {
+            let uri = input_path.to_string_lossy().replace('\\', "/");
+            format!(
+                "{}\n",
+                serde_json::to_string_pretty(&report.to_sarif(&uri))?
+            )
+        }
+        _ => report.to_string(),
+    };
+    write_output(&result, matches.get_one::<String>("output"), quiet)?;
+
+    if !quiet {
+        eprintln!(
+            "{} error(s), {} warning(s), {} note(s)",
+            report.count(Severity::Error),
+            report.count(Severity::Warning),
+            report.count(Severity::Note)
+        );
+    }
+    if report.count(Severity::Error) > 0 {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn run_validate(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let input_path = validate_input(matches.get_one::<String>("input").unwrap());
+    let content = std::fs::read_to_string(&input_path)?;
+    let level = if matches.get_flag("lenient") {
+        ValidationLevel::Lenient
+    } else {
+        ValidationLevel::Strict
+    };
+    let quiet = matches.get_flag("quiet");
+    let issues = Validator::new(level).validate_str(&content)?;
+
+    let result: String = issues.iter().map(|issue| format!("{}\n", issue)).collect();
+    write_output(&result, matches.get_one::<String>("output"), quiet)?;
+
+    if !quiet {
+        match issues.len() {
+            0 => eprintln!("{}: valid", input_path.display()),
+            count => eprintln!("{}: {} issue(s)", input_path.display(), count),
+        }
+    }
+    if !issues.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn run_deps(matches: &ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let input_path = validate_input(matches.get_one::<String>("input").unwrap());
+    let notebook = Notebook::from_file(&input_path)?;
+    let graph = DependencyGraph::build(&notebook);
+
+    let result = match matches.get_one::<String>("format").map(String::as_str) {
+        Some("dot") => graph.to_dot(),
+        _ => graph.to_string(),
+    };
+    write_output(
+        &result,
+        matches.get_one::<String>("output"),
+        matches.get_flag("quiet"),
+    )?;
+    Ok(())
+}
+
+/// Prints an error with its location in the notebook and a snippet of the
+/// surrounding JSON, colorized when stderr is a terminal.
+fn report_error(error: &(dyn std::error::Error + 'static)) {
+    let color = std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    let paint = |style: &str, text: &str| {
+        if color {
+            format!("\x1b[{}m{}\x1b[0m", style, text)
+        } else {
+            text.to_string()
+        }
+    };
+
+    eprintln!("{} {}", paint("1;31", "Error:"), error);
+    let Some(location) = error
+        .downcast_ref::<JupyterError>()
+        .and_then(JupyterError::location)
+    else {
+        return;
+    };
+    if let (Some(line), Some(column)) = (location.line, location.column) {
+        eprintln!(
+            "  {} line {}, column {}",
+            paint("1;34", "-->"),
+            line,
+            column
+        );
+    }
+    if let Some(snippet) = &location.snippet {
+        let gutter = location
+            .line
+            .map(|line| line.to_string())
+            .unwrap_or_default();
+        let padding = " ".repeat(gutter.len());
+        eprintln!(" {} {}", padding, paint("1;34", "|"));
+        eprintln!(
+            " {} {} {}",
+            paint("1;34", &gutter),
+            paint("1;34", "|"),
+            snippet.text
+        );
+        eprintln!(
+            " {} {} {}{}",
+            padding,
+            paint("1;34", "|"),
+            " ".repeat(snippet.caret),
+            paint("1;31", "^")
+        );
+    }
+}
+
+fn main() {
+    if let Err(error) = run() {
+        report_error(error.as_ref());
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
     let matches = Command::new("jupyter2llm")
         .version("0.1.0")
         .author("Your Name <your.email@example.com>")
         .about("Convert Jupyter notebooks to LLM-optimized text")
+        .subcommand_negates_reqs(true)
+        .args_conflicts_with_subcommands(true)
+        .subcommand(
+            Command::new("deps")
+                .about("Print the cross-cell dependency graph of defined and imported names")
+                .arg(
+                    Arg::new("input")
+                        .help("Path to the Jupyter notebook file (.ipynb)")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("format")
+                        .short('f')
+                        .long("format")
+                        .help("Output format")
+                        .value_parser(["table", "dot"])
+                        .default_value("table"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("Output file path (default: stdout)")
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new("quiet")
+                        .short('q')
+                        .long("quiet")
+                        .help("Suppress informational messages")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("script")
+                .about("Export the code cells as a standalone Python script in dependency order")
+                .arg(
+                    Arg::new("input")
+                        .help("Path to the Jupyter notebook file (.ipynb)")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("Output file path (default: stdout)")
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new("quiet")
+                        .short('q')
+                        .long("quiet")
+                        .help("Suppress informational messages")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("requirements")
+                .about("Draft a requirements.txt from imports, install lines and pip freeze outputs")
+                .arg(
+                    Arg::new("input")
+                        .help("Path to the Jupyter notebook file (.ipynb)")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("Output file path (default: stdout)")
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new("quiet")
+                        .short('q')
+                        .long("quiet")
+                        .help("Suppress informational messages")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("lint")
+                .about("Check the notebook for leftover errors, secrets, huge outputs and other problems")
+                .arg(
+                    Arg::new("input")
+                        .help("Path to the Jupyter notebook file (.ipynb)")
+                        .required_unless_present("list-rules")
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("format")
+                        .short('f')
+                        .long("format")
+                        .help("Output format")
+                        .value_parser(["text", "json", "sarif"])
+                        .default_value("text"),
+                )
+                .arg(
+                    Arg::new("rule")
+                        .long("rule")
+                        .help("Severity of a rule as ID=LEVEL, with LEVEL error, warning, note or off (repeatable)")
+                        .value_name("ID=LEVEL")
+                        .action(clap::ArgAction::Append),
+                )
+                .arg(
+                    Arg::new("list-rules")
+                        .long("list-rules")
+                        .help("List the available rules with their default severity and exit")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("Output file path (default: stdout)")
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new("quiet")
+                        .short('q')
+                        .long("quiet")
+                        .help("Suppress informational messages")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("validate")
+                .about("Check the notebook against the nbformat 4 schema rules")
+                .arg(
+                    Arg::new("input")
+                        .help("Path to the Jupyter notebook file (.ipynb)")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("lenient")
+                        .long("lenient")
+                        .help("Only report problems that prevent a conversion")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("Output file path (default: stdout)")
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new("quiet")
+                        .short('q')
+                        .long("quiet")
+                        .help("Suppress informational messages")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
         .arg(
             Arg::new("input")
-                .help("Path to the Jupyter notebook file (.ipynb)")
+                .help("Jupyter notebook files (.ipynb), directories or glob patterns")
                 .required(true)
+                .num_args(1..)
                 .index(1),
         )
         .arg(
             Arg::new("output")
                 .short('o')
                 .long("output")
-                .help("Output file path (default: stdout)")
+                .help("Output file path (default: stdout); several notebooks are concatenated")
                 .value_name("FILE"),
         )
+        .arg(
+            Arg::new("output-dir")
+                .long("output-dir")
+                .help("Write one file per notebook into this directory, mirroring the input directories")
+                .value_name("DIR")
+                .conflicts_with("output"),
+        )
+        .arg(
+            Arg::new("name-pattern")
+                .long("name-pattern")
+                .help("File name of each notebook under --output-dir, with {stem} and {name} placeholders")
+                .value_name("PATTERN")
+                .default_value(batch::DEFAULT_NAME_PATTERN),
+        )
+        .arg(
+            Arg::new("bundle")
+                .short('b')
+                .long("bundle")
+                .help("Bundle the notebooks into one document with a manifest of files, cell counts and token estimates")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("output-dir"),
+        )
+        .arg(
+            Arg::new("include-related")
+                .long("include-related")
+                .help("Add the .py, .md, .toml and requirements*.txt files next to the notebooks to the bundle")
+                .action(clap::ArgAction::SetTrue)
+                .requires("bundle"),
+        )
+        .arg(
+            Arg::new("max-file-size")
+                .long("max-file-size")
+                .help("Skip related files larger than this many bytes")
+                .value_name("BYTES")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("65536"),
+        )
+        .arg(
+            Arg::new("max-related-size")
+                .long("max-related-size")
+                .help("Skip related files once their combined size exceeds this many bytes")
+                .value_name("BYTES")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("1048576"),
+        )
+        .arg(
+            Arg::new("include-references")
+                .long("include-references")
+                .help("Append local modules imported by the notebooks and the targets of %run and %load, following their references in turn")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("reference-depth")
+                .long("reference-depth")
+                .help("How many references deep to follow")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("2")
+                .requires("include-references"),
+        )
+        .arg(
+            Arg::new("max-reference-size")
+                .long("max-reference-size")
+                .help("Skip referenced files once their combined size exceeds this many bytes")
+                .value_name("BYTES")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("262144")
+                .requires("include-references"),
+        )
+        .arg(
+            Arg::new("preview-data")
+                .long("preview-data")
+                .help("Append previews of the CSV, TSV, JSON, JSON Lines, Parquet and Excel files named in code cells")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("sample-rows")
+                .long("sample-rows")
+                .help("Rows sampled from each CSV or TSV file")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("5")
+                .requires("preview-data"),
+        )
+        .arg(
+            Arg::new("max-data-size")
+                .long("max-data-size")
+                .help("Bytes read from the start of each data file")
+                .value_name("BYTES")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("65536")
+                .requires("preview-data"),
+        )
+        .arg(
+            Arg::new("jobs")
+                .short('j')
+                .long("jobs")
+                .help("Number of notebooks converted in parallel (default: number of CPU cores)")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize)),
+        )
         .arg(
             Arg::new("include-outputs")
                 .short('O')
@@ -50,6 +666,116 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .help("Copy output to clipboard (macOS only)")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("compact-tracebacks")
+                .short('t')
+                .long("compact-tracebacks")
+                .help("Keep only notebook frames and the final library frame of error tracebacks")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("errors-only")
+                .short('e')
+                .long("errors-only")
+                .help("Only render failing cells with their tracebacks, preceding code cells and notebook metadata")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("error-context")
+                .long("error-context")
+                .help(format!(
+                    "Number of preceding code cells shown with each failing cell (default {})",
+                    DEFAULT_ERROR_CONTEXT
+                ))
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("execution-order")
+                .long("execution-order")
+                .help("Render code cells in the order they were executed instead of document order")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("outline")
+                .long("outline")
+                .help("Start the output with an outline of headings and code cells")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("section")
+                .short('s')
+                .long("section")
+                .help("Only convert the section under this heading path, e.g. \"Training/Evaluation\"")
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::new("context-for")
+                .long("context-for")
+                .help("Only convert this code cell and the cells it depends on through imported and defined names")
+                .value_name("CELL")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("list-sections")
+                .long("list-sections")
+                .help("List the available section paths and exit")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("redact")
+                .short('r')
+                .long("redact")
+                .help("Redact API keys, tokens, passwords and private keys before rendering")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("redact-rule")
+                .long("redact-rule")
+                .help("Additional redaction rule as NAME=REGEX (implies --redact, repeatable)")
+                .value_name("NAME=REGEX")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("scrub-pii")
+                .long("scrub-pii")
+                .help("Replace emails, phone numbers, IP addresses and card numbers in outputs with consistent placeholders")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("pii-column")
+                .long("pii-column")
+                .help("DataFrame column whose values are scrubbed from outputs (implies --scrub-pii, repeatable)")
+                .value_name("NAME")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("anonymize")
+                .short('a')
+                .long("anonymize")
+                .help("Rewrite home directories, site-packages and environment paths to placeholders and strip author metadata")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("anonymize-user")
+                .long("anonymize-user")
+                .help("Username replaced with <USER> (implies --anonymize, repeatable)")
+                .value_name("NAME")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("anonymize-host")
+                .long("anonymize-host")
+                .help("Hostname replaced with <HOST> (implies --anonymize, repeatable)")
+                .value_name("NAME")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("recover")
+                .long("recover")
+                .help("Render unknown or malformed cells as placeholders and salvage truncated files instead of failing")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("quiet")
                 .short('q')
@@ -59,31 +785,96 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         )
         .get_matches();
 
-    let input_path = matches.get_one::<String>("input").unwrap();
+    match matches.subcommand() {
+        Some(("deps", deps_matches)) => return run_deps(deps_matches),
+        Some(("script", script_matches)) => return run_script(script_matches),
+        Some(("lint", lint_matches)) => return run_lint(lint_matches),
+        Some(("validate", validate_matches)) => return run_validate(validate_matches),
+        Some(("requirements", requirements_matches)) => {
+            return run_requirements(requirements_matches)
+        }
+        _ => {}
+    }
+
+    let inputs: Vec<&String> = matches.get_many::<String>("input").unwrap().collect();
     let output_path = matches.get_one::<String>("output");
+    let output_dir = matches.get_one::<String>("output-dir");
+    let name_pattern = matches.get_one::<String>("name-pattern").unwrap();
+    let bundle = matches.get_flag("bundle");
+    let include_related = matches.get_flag("include-related");
+    let max_file_size = *matches.get_one::<u64>("max-file-size").unwrap();
+    let max_related_size = *matches.get_one::<u64>("max-related-size").unwrap();
+    let include_references = matches.get_flag("include-references");
+    let preview_data = matches.get_flag("preview-data");
+    let data_limits = DataLimits {
+        sample_rows: *matches.get_one::<usize>("sample-rows").unwrap(),
+        max_bytes: *matches.get_one::<u64>("max-data-size").unwrap(),
+    };
+    let reference_limits = ReferenceLimits {
+        max_depth: *matches.get_one::<usize>("reference-depth").unwrap(),
+        max_bytes: *matches.get_one::<u64>("max-reference-size").unwrap(),
+    };
+    let jobs = matches
+        .get_one::<usize>("jobs")
+        .copied()
+        .unwrap_or_else(|| {
+            std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+        });
     let include_outputs = matches.get_flag("include-outputs");
     let include_metadata = matches.get_flag("include-metadata");
     let llm_ready = matches.get_flag("llm-ready");
     let copy_clipboard = matches.get_flag("copy-clipboard");
+    let compact_tracebacks = matches.get_flag("compact-tracebacks");
+    let errors_only = matches.get_flag("errors-only");
+    let error_context = matches.get_one::<usize>("error-context");
+    let execution_order = matches.get_flag("execution-order");
+    let include_outline = matches.get_flag("outline");
+    let section = matches.get_one::<String>("section");
+    let context_for = matches.get_one::<usize>("context-for");
+    let list_sections = matches.get_flag("list-sections");
+    let redact_rules: Vec<&String> = matches
+        .get_many::<String>("redact-rule")
+        .unwrap_or_default()
+        .collect();
+    let redact = matches.get_flag("redact") || !redact_rules.is_empty();
+    let pii_columns: Vec<&String> = matches
+        .get_many::<String>("pii-column")
+        .unwrap_or_default()
+        .collect();
+    let scrub_pii = matches.get_flag("scrub-pii") || !pii_columns.is_empty();
+    let anonymize_users: Vec<&String> = matches
+        .get_many::<String>("anonymize-user")
+        .unwrap_or_default()
+        .collect();
+    let anonymize_hosts: Vec<&String> = matches
+        .get_many::<String>("anonymize-host")
+        .unwrap_or_default()
+        .collect();
+    let anonymize =
+        matches.get_flag("anonymize") || !anonymize_users.is_empty() || !anonymize_hosts.is_empty();
+    let recover = matches.get_flag("recover");
     let quiet = matches.get_flag("quiet");
 
-    // Validate input file
-    let input_path = PathBuf::from(input_path);
-    if !input_path.exists() {
-        eprintln!(
-            "Error: Input file '{}' does not exist",
-            input_path.display()
-        );
-        std::process::exit(1);
-    }
-
-    if input_path.extension().map_or(true, |ext| ext != "ipynb") {
-        eprintln!("Error: Input file must have .ipynb extension");
+    let batch =
+        inputs.len() > 1 || output_dir.is_some() || bundle || !Path::new(inputs[0]).is_file();
+    if batch && (list_sections || copy_clipboard) {
+        eprintln!("Error: --list-sections and --copy-clipboard take a single notebook");
         std::process::exit(1);
     }
 
-    if !quiet {
-        eprintln!("Converting notebook: {}", input_path.display());
+    if list_sections {
+        let input_path = validate_input(inputs[0]);
+        let notebook = Notebook::from_file(&input_path)?;
+        for section in sections::sections(&notebook) {
+            println!(
+                "{}{}  (cells {}-{})",
+                "  ".repeat(section.level.saturating_sub(1)),
+                section.path_string(),
+                section.start + 1,
+                section.end
+            );
+        }
+        return Ok(());
     }
 
     // Handle llm-ready flag (overrides individual flags)
@@ -91,12 +882,133 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let final_include_metadata = llm_ready || include_metadata;
 
     // Create converter with specified options
-    let converter = JupyterConverter::new()
+    let mut converter = JupyterConverter::new()
         .with_outputs(final_include_outputs)
-        .with_metadata(final_include_metadata);
+        .with_metadata(final_include_metadata)
+        .with_outline(include_outline)
+        .with_errors_only(errors_only)
+        .with_recovery(recover);
+    if execution_order {
+        converter = converter.with_cell_order(CellOrder::Execution);
+    }
+    if compact_tracebacks {
+        converter = converter.with_traceback_mode(TracebackMode::Compact);
+    }
+    if let Some(section) = section {
+        converter = converter.with_section(section);
+    }
+    if let Some(&cells) = error_context {
+        converter = converter.with_error_context(cells);
+    }
+    if let Some(&cell_number) = context_for {
+        converter = converter.with_context_for(cell_number);
+    }
+    if include_references {
+        converter = converter.with_references(reference_limits);
+    }
+    if preview_data {
+        converter = converter.with_data_files(data_limits);
+    }
+    if anonymize {
+        let anonymizer = anonymize_users
+            .into_iter()
+            .fold(Anonymizer::new(), |anonymizer, user| {
+                anonymizer.with_username(user)
+            });
+        let anonymizer = anonymize_hosts
+            .into_iter()
+            .fold(anonymizer, |anonymizer, host| {
+                anonymizer.with_hostname(host)
+            });
+        converter = converter.with_anonymizer(anonymizer);
+    }
+
+    if redact {
+        let mut redactor = Redactor::new();
+        for rule in redact_rules {
+            let Some((name, pattern)) = rule.split_once('=') else {
+                eprintln!(
+                    "Error: Redaction rule '{}' must have the form NAME=REGEX",
+                    rule
+                );
+                std::process::exit(1);
+            };
+            redactor = redactor.with_rule(name, pattern)?;
+        }
+        converter = converter.with_redactor(redactor);
+    }
+    if scrub_pii {
+        let scrubber = pii_columns
+            .into_iter()
+            .fold(PiiScrubber::new(), |scrubber, column| {
+                scrubber.with_column(column)
+            });
+        converter = converter.with_pii_scrubber(scrubber);
+    }
+
+    if batch {
+        let inputs = batch::collect_inputs(&inputs)?;
+        if !quiet {
+            eprintln!("Converting {} notebook(s)", inputs.len());
+        }
+        if bundle {
+            let bundler = Bundler::new(&converter)
+                .with_related_files(include_related)
+                .with_max_file_bytes(max_file_size)
+                .with_max_related_bytes(max_related_size);
+            let entries = match output_path {
+                Some(output_path) => {
+                    let mut file = BufWriter::new(File::create(output_path)?);
+                    let entries = bundler.bundle(&inputs, &mut file)?;
+                    file.flush()?;
+                    if !quiet {
+                        eprintln!("Bundle written to: {}", output_path);
+                    }
+                    entries
+                }
+                None => {
+                    let mut stdout = BufWriter::new(std::io::stdout().lock());
+                    let entries = bundler.bundle(&inputs, &mut stdout)?;
+                    stdout.flush()?;
+                    entries
+                }
+            };
+            report_bundle(entries, quiet);
+            return Ok(());
+        }
+        let report = match (output_dir, output_path) {
+            (Some(dir), _) => batch_runner(&converter, jobs, quiet).run(
+                &inputs,
+                BatchOutput::Directory {
+                    dir: PathBuf::from(dir),
+                    pattern: name_pattern.clone(),
+                },
+            )?,
+            (None, Some(output_path)) => {
+                let mut file = BufWriter::new(File::create(output_path)?);
+                let report = batch_runner(&converter, jobs, quiet)
+                    .run(&inputs, BatchOutput::Concatenate(&mut file))?;
+                file.flush()?;
+                report
+            }
+            (None, None) => {
+                let mut stdout = BufWriter::new(std::io::stdout().lock());
+                let report = batch_runner(&converter, jobs, quiet)
+                    .run(&inputs, BatchOutput::Concatenate(&mut stdout))?;
+                stdout.flush()?;
+                report
+            }
+        };
+        report_batch(report, quiet);
+        return Ok(());
+    }
+
+    let input_path = validate_input(inputs[0]);
+    if !quiet {
+        eprintln!("Converting notebook: {}", input_path.display());
+    }
 
-    // Convert the notebook
-    let result = converter.convert_file(&input_path)?;
+    let (notebook, mut diagnostics) = load_notebook(&input_path, recover)?;
 
     // Handle clipboard copying (macOS only)
     if copy_clipboard {
@@ -107,10 +1019,15 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             let mut child = cmd.stdin(std::process::Stdio::piped()).spawn()?;
             {
                 let stdin = child.stdin.as_mut().expect("Failed to open stdin");
-                use std::io::Write;
-                stdin.write_all(result.as_bytes())?;
+                diagnostics.extend(converter.convert_loaded_to_writer(
+                    &notebook,
+                    &input_path,
+                    &mut *stdin,
+                )?);
+                write_appendix(&converter, &input_path, &notebook, stdin, &mut diagnostics)?;
             }
             child.wait()?;
+            report_diagnostics(&mut diagnostics, quiet);
             if !quiet {
                 eprintln!("Output copied to clipboard!");
             }
@@ -122,10 +1039,26 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
-    // Write output
+    // Stream the output cell by cell. Secrets and personal data are replaced
+    // while rendering, including in referenced files and data previews, and
+    // reported as diagnostics.
     match output_path {
         Some(output_path) => {
-            std::fs::write(output_path, &result)?;
+            let mut file = BufWriter::new(File::create(output_path)?);
+            diagnostics.extend(converter.convert_loaded_to_writer(
+                &notebook,
+                &input_path,
+                &mut file,
+            )?);
+            write_appendix(
+                &converter,
+                &input_path,
+                &notebook,
+                &mut file,
+                &mut diagnostics,
+            )?;
+            file.flush()?;
+            report_diagnostics(&mut diagnostics, quiet);
             if !quiet {
                 if llm_ready {
                     eprintln!("LLM-ready output written to: {}", output_path);
@@ -135,7 +1068,22 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
         None => {
-            println!("{}", result);
+            let mut stdout = BufWriter::new(std::io::stdout().lock());
+            diagnostics.extend(converter.convert_loaded_to_writer(
+                &notebook,
+                &input_path,
+                &mut stdout,
+            )?);
+            write_appendix(
+                &converter,
+                &input_path,
+                &notebook,
+                &mut stdout,
+                &mut diagnostics,
+            )?;
+            writeln!(stdout)?;
+            stdout.flush()?;
+            report_diagnostics(&mut diagnostics, quiet);
         }
     }
 
@@ -0,0 +1,130 @@
+//! Table of contents rendered ahead of the cells.
+//!
+//! The outline lists the markdown heading hierarchy with the cell where each
+//! section starts, and the code cells of every section with a one-line summary.
+
+use crate::sections;
+use crate::{Cell, Notebook};
+
+const MAX_SUMMARY_CHARS: usize = 60;
+
+/// Renders the outline block for the metadata header.
+pub fn render_outline(notebook: &Notebook) -> String {
+    render_outline_with_cuts(notebook).0
+}
+
+/// Like `render_outline`, also returning the code cells whose summaries
+/// were cut to `MAX_SUMMARY_CHARS`.
+pub fn render_outline_with_cuts(notebook: &Notebook) -> (String, Vec<usize>) {
+    let sections = sections::sections(notebook);
+    let mut outline = String::from("**Outline**:\n");
+    let mut cut = Vec::new();
+    let mut depth = 0;
+    let mut next_section = sections.iter().peekable();
+
+    for (index, cell) in notebook.cells.iter().enumerate() {
+        while let Some(section) = next_section.next_if(|section| section.start == index) {
+            depth = section.path.len();
+            outline.push_str(&format!(
+                "{}- {} (Cell {})\n",
+                "  ".repeat(depth - 1),
+                section.title,
+                index + 1
+            ));
+        }
+
+        if cell.cell_type == "code" {
+            let (summary, was_cut) = summarize(cell);
+            if was_cut {
+                cut.push(index);
+            }
+            outline.push_str(&format!(
+                "{}- Cell {}: {}\n",
+                "  ".repeat(depth),
+                index + 1,
+                summary
+            ));
+        }
+    }
+
+    (outline, cut)
+}
+
+/// Summarizes a code cell by the functions and classes it defines, falling
+/// back to its first meaningful line.
+pub fn summarize_code_cell(cell: &Cell) -> String {
+    summarize(cell).0
+}
+
+/// The summary of a code cell and whether its line was cut.
+fn summarize(cell: &Cell) -> (String, bool) {
+    let definitions: Vec<String> = cell
+        .source_lines()
+        .filter_map(|line| {
+            let line = line.strip_prefix("async ").unwrap_or(line);
+            let (keyword, rest) = line
+                .strip_prefix("def ")
+                .map(|rest| ("def", rest))
+                .or_else(|| line.strip_prefix("class ").map(|rest| ("class", rest)))?;
+            let name: String = rest
+                .trim_start()
+                .chars()
+                .take_while(|c| c.is_alphanumeric() || *c == '_')
+                .collect();
+            (!name.is_empty()).then(|| format!("{} `{}`", keyword, name))
+        })
+        .collect();
+    if !definitions.is_empty() {
+        return (definitions.join(", "), false);
+    }
+
+    let first_line = cell.source_lines().map(str::trim).find(|line| {
+        !line.is_empty()
+            && !line.starts_with('#')
+            && !line.starts_with('%')
+            && !line.starts_with('!')
+    });
+
+    match first_line {
+        Some(line) if line.chars().count() > MAX_SUMMARY_CHARS => {
+            let truncated: String = line.chars().take(MAX_SUMMARY_CHARS).collect();
+            (format!("`{}...`", truncated), true)
+        }
+        Some(line) => (format!("`{}`", line), false),
+        None => ("(empty)".to_string(), false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(lines: &[&str]) -> Cell {
+        Cell {
+            cell_type: "code".to_string(),
+            source: lines.iter().map(|line| line.to_string()).collect(),
+            metadata: Default::default(),
+            outputs: Some(Vec::new()),
+            execution_count: None,
+        }
+    }
+
+    #[test]
+    fn test_summary_prefers_definitions() {
+        let cell = code(&[
+            "import os\n",
+            "def load(path):\n",
+            "    pass\n",
+            "class Model:\n",
+            "    pass",
+        ]);
+        assert_eq!(summarize_code_cell(&cell), "def `load`, class `Model`");
+    }
+
+    #[test]
+    fn test_summary_skips_comments_and_magics() {
+        let cell = code(&["# setup\n", "%matplotlib inline\n", "\n", "df = load()"]);
+        assert_eq!(summarize_code_cell(&cell), "`df = load()`");
+        assert_eq!(summarize_code_cell(&code(&[])), "(empty)");
+    }
+}
@@ -0,0 +1,521 @@
+//! PII scrubbing for cell outputs.
+//!
+//! Stream text, `text/plain` and `text/html` outputs are scanned for emails,
+//! phone numbers, IP addresses and credit-card numbers, and for the values of
+//! user-named DataFrame columns. With pseudonymization enabled, the same value
+//! maps to the same placeholder (`<EMAIL_1>`, `<EMAIL_2>`, ...) across the
+//! whole notebook so that relationships between rows stay visible.
+
+use crate::Notebook;
+use regex::{Captures, Regex};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::OnceLock;
+
+struct Patterns {
+    email: Regex,
+    credit_card: Regex,
+    phone: Regex,
+    ipv4: Regex,
+    ipv6: Regex,
+    html_table: Regex,
+    html_row: Regex,
+    html_cell: Regex,
+}
+
+fn patterns() -> &'static Patterns {
+    static PATTERNS: OnceLock<Patterns> = OnceLock::new();
+    PATTERNS.get_or_init(|| Patterns {
+        email: Regex::new(r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}\b").unwrap(),
+        credit_card: Regex::new(r"\b\d(?:[ -]?\d){12,18}\b").unwrap(),
+        // Separators are spaces, not `\s`, so numbers on consecutive lines
+        // of an output are not read as one phone number.
+        phone: Regex::new(r"(?:\+\d{1,3}[ .-]?)?(?:\(\d{3}\) ?|\b\d{3}[ .-])\d{3}[ .-]\d{4}\b")
+            .unwrap(),
+        ipv4: Regex::new(
+            r"\b(?:(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)\.){3}(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)\b",
+        )
+        .unwrap(),
+        ipv6: Regex::new(r"\b(?:[0-9A-Fa-f]{1,4}:){7}[0-9A-Fa-f]{1,4}\b").unwrap(),
+        html_table: Regex::new(r"(?is)<table\b.*?</table>").unwrap(),
+        html_row: Regex::new(r"(?is)<tr\b[^>]*>.*?</tr>").unwrap(),
+        html_cell: Regex::new(r"(?is)(<t[hd]\b[^>]*>)(.*?)(</t[hd]>)").unwrap(),
+    })
+}
+
+/// Luhn checksum over the digits of a candidate card number.
+fn passes_luhn(digits: &[u32]) -> bool {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(position, &digit)| {
+            if !position.is_multiple_of(2) {
+                let doubled = digit * 2;
+                if doubled > 9 {
+                    doubled - 9
+                } else {
+                    doubled
+                }
+            } else {
+                digit
+            }
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PiiFinding {
+    pub cell_index: usize,
+    /// Placeholder label, e.g. `EMAIL` or the upper-cased column name.
+    pub kind: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PiiReport {
+    pub findings: Vec<PiiFinding>,
+}
+
+impl PiiReport {
+    pub fn is_empty(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    pub fn total(&self) -> usize {
+        self.findings.iter().map(|finding| finding.count).sum()
+    }
+}
+
+impl fmt::Display for PiiReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for finding in &self.findings {
+            writeln!(
+                f,
+                "Cell {} (outputs): {} x{}",
+                finding.cell_index + 1,
+                finding.kind,
+                finding.count
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PiiScrubber {
+    columns: Vec<String>,
+    pseudonymize: bool,
+}
+
+impl Default for PiiScrubber {
+    fn default() -> Self {
+        Self {
+            columns: Vec::new(),
+            pseudonymize: true,
+        }
+    }
+}
+
+impl PiiScrubber {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Scrubs every value of the named column in DataFrame outputs.
+    pub fn with_column<S: Into<String>>(mut self, name: S) -> Self {
+        self.columns.push(name.into());
+        self
+    }
+
+    /// Numbers placeholders per distinct value (`<EMAIL_1>`) instead of using
+    /// one generic placeholder (`<EMAIL>`) for every match.
+    pub fn with_pseudonymize(mut self, pseudonymize: bool) -> Self {
+        self.pseudonymize = pseudonymize;
+        self
+    }
+
+    /// Scrubs stream, `text/plain` and `text/html` outputs in place.
+    pub fn scrub_notebook(&self, notebook: &mut Notebook) -> PiiReport {
+        let mut state = self.state();
+
+        for (index, cell) in notebook.cells.iter_mut().enumerate() {
+            state.cell_index = index;
+            for output in cell.outputs.iter_mut().flatten() {
+                if let Some(text) = &mut output.text {
+                    state.scrub_lines(text, false);
+                }
+                for (mime, value) in output.data.iter_mut().flatten() {
+                    match mime.as_str() {
+                        "text/plain" => state.scrub_value(value, false),
+                        "text/html" => state.scrub_value(value, true),
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        PiiReport {
+            findings: state
+                .tally
+                .into_iter()
+                .map(|((cell_index, kind), count)| PiiFinding {
+                    cell_index,
+                    kind,
+                    count,
+                })
+                .collect(),
+        }
+    }
+
+    /// Scrubs a single string, returning the number of replacements per kind.
+    pub fn scrub_text(&self, text: &mut String) -> Vec<(String, usize)> {
+        let mut state = self.state();
+        *text = state.scrub_text(text, false);
+        state
+            .tally
+            .into_iter()
+            .map(|((_, kind), count)| (kind, count))
+            .collect()
+    }
+
+    fn state(&self) -> ScrubState<'_> {
+        ScrubState {
+            scrubber: self,
+            pseudonyms: HashMap::new(),
+            next_id: HashMap::new(),
+            tally: BTreeMap::new(),
+            cell_index: 0,
+        }
+    }
+}
+
+struct ScrubState<'a> {
+    scrubber: &'a PiiScrubber,
+    pseudonyms: HashMap<(String, String), String>,
+    next_id: HashMap<String, usize>,
+    tally: BTreeMap<(usize, String), usize>,
+    cell_index: usize,
+}
+
+impl ScrubState<'_> {
+    fn placeholder(&mut self, kind: &str, value: &str) -> String {
+        *self
+            .tally
+            .entry((self.cell_index, kind.to_string()))
+            .or_default() += 1;
+
+        if !self.scrubber.pseudonymize {
+            return format!("<{}>", kind);
+        }
+        let key = (kind.to_string(), value.to_string());
+        if let Some(existing) = self.pseudonyms.get(&key) {
+            return existing.clone();
+        }
+        let id = self.next_id.entry(kind.to_string()).or_default();
+        *id += 1;
+        let placeholder = format!("<{}_{}>", kind, id);
+        self.pseudonyms.insert(key, placeholder.clone());
+        placeholder
+    }
+
+    /// Scrubs a multi-line field stored as a list of strings. Entries are
+    /// joined when they are newline-terminated so that tables spanning several
+    /// entries are seen as a whole.
+    fn scrub_lines(&mut self, lines: &mut Vec<String>, html: bool) {
+        let joinable = lines.iter().rev().skip(1).all(|line| line.ends_with('\n'));
+        if joinable && lines.len() > 1 {
+            let scrubbed = self.scrub_text(&lines.concat(), html);
+            *lines = scrubbed.split_inclusive('\n').map(str::to_string).collect();
+        } else {
+            for line in lines.iter_mut() {
+                *line = self.scrub_text(line, html);
+            }
+        }
+    }
+
+    fn scrub_value(&mut self, value: &mut Value, html: bool) {
+        match value {
+            Value::String(text) => *text = self.scrub_text(text, html),
+            Value::Array(items) => {
+                let mut lines: Vec<String> = items
+                    .iter()
+                    .filter_map(|item| item.as_str().map(str::to_string))
+                    .collect();
+                if lines.len() == items.len() {
+                    self.scrub_lines(&mut lines, html);
+                    *items = lines.into_iter().map(Value::String).collect();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn scrub_text(&mut self, text: &str, html: bool) -> String {
+        let mut text = text.to_string();
+        for column in &self.scrubber.columns {
+            text = if html {
+                self.scrub_html_column(&text, column)
+            } else {
+                self.scrub_text_column(&text, column)
+            };
+        }
+
+        let patterns = patterns();
+        text = self.replace(&patterns.email, &text, |_| Some("EMAIL"));
+        text = self.replace(&patterns.credit_card, &text, |value| {
+            let digits: Vec<u32> = value.chars().filter_map(|c| c.to_digit(10)).collect();
+            passes_luhn(&digits).then_some("CREDIT_CARD")
+        });
+        text = self.replace(&patterns.phone, &text, |_| Some("PHONE"));
+        text = self.replace(&patterns.ipv6, &text, |_| Some("IP"));
+        self.replace(&patterns.ipv4, &text, |_| Some("IP"))
+    }
+
+    fn replace(
+        &mut self,
+        regex: &Regex,
+        text: &str,
+        classify: impl Fn(&str) -> Option<&'static str>,
+    ) -> String {
+        regex
+            .replace_all(text, |captures: &Captures| {
+                let value = &captures[0];
+                match classify(value) {
+                    Some(kind) => self.placeholder(kind, value),
+                    None => value.to_string(),
+                }
+            })
+            .into_owned()
+    }
+
+    /// Scrubs a column of a fixed-width pandas repr. Values are right-aligned
+    /// under the header, so the column spans from the end of the previous
+    /// header token to the end of the column name.
+    fn scrub_text_column(&mut self, text: &str, column: &str) -> String {
+        let kind = column_kind(column);
+        let mut span: Option<ColumnSpan> = None;
+        let mut result = String::with_capacity(text.len());
+
+        for line in text.split_inclusive('\n') {
+            let body = line.trim_end_matches(['\n', '\r']);
+            let chars: Vec<char> = body.chars().collect();
+
+            if body.trim().is_empty() {
+                span = None;
+            } else if let Some(ColumnSpan {
+                start,
+                end,
+                after_index,
+            }) = span
+            {
+                // The index column has no header, so the first column starts
+                // right after the index value of each row.
+                let start = if after_index {
+                    chars
+                        .iter()
+                        .position(|c| !c.is_whitespace())
+                        .map_or(0, |first| {
+                            chars[first..]
+                                .iter()
+                                .position(|c| c.is_whitespace())
+                                .map_or(chars.len(), |len| first + len + 1)
+                        })
+                } else {
+                    start
+                };
+                if chars.len() > start && start < end && !body.trim_start().starts_with('[') {
+                    let end = end.min(chars.len());
+                    let value: String = chars[start..end].iter().collect();
+                    let trimmed = value.trim();
+                    if !trimmed.is_empty() && trimmed != "..." {
+                        let placeholder = self.placeholder(&kind, trimmed);
+                        let prefix: String = chars[..start].iter().collect();
+                        let suffix: String = chars[end..].iter().collect();
+                        result.push_str(&prefix);
+                        result.push_str(&format!("{:>width$}", placeholder, width = end - start));
+                        result.push_str(&suffix);
+                        result.push_str(&line[body.len()..]);
+                        continue;
+                    }
+                }
+            } else {
+                span = header_span(&chars, column);
+            }
+
+            result.push_str(line);
+        }
+
+        result
+    }
+
+    /// Scrubs a column of an HTML table, located by its `<th>` position.
+    fn scrub_html_column(&mut self, html: &str, column: &str) -> String {
+        let kind = column_kind(column);
+        let patterns = patterns();
+
+        patterns
+            .html_table
+            .replace_all(html, |table: &Captures| {
+                let mut position: Option<usize> = None;
+                patterns
+                    .html_row
+                    .replace_all(&table[0], |row: &Captures| {
+                        let Some(target) = position else {
+                            position = patterns
+                                .html_cell
+                                .captures_iter(&row[0])
+                                .position(|cell| cell[2].trim().eq_ignore_ascii_case(column));
+                            return row[0].to_string();
+                        };
+                        let mut index = 0;
+                        patterns
+                            .html_cell
+                            .replace_all(&row[0], |cell: &Captures| {
+                                let current = index;
+                                index += 1;
+                                let value = cell[2].trim();
+                                if current == target && !value.is_empty() {
+                                    format!(
+                                        "{}{}{}",
+                                        &cell[1],
+                                        self.placeholder(&kind, value),
+                                        &cell[3]
+                                    )
+                                } else {
+                                    cell[0].to_string()
+                                }
+                            })
+                            .into_owned()
+                    })
+                    .into_owned()
+            })
+            .into_owned()
+    }
+}
+
+fn column_kind(column: &str) -> String {
+    column
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ColumnSpan {
+    start: usize,
+    end: usize,
+    /// The column is the first one after an unlabeled index column.
+    after_index: bool,
+}
+
+/// Finds the character span of `column` in a header line of a pandas repr.
+fn header_span(chars: &[char], column: &str) -> Option<ColumnSpan> {
+    let mut previous_end = 0;
+    let mut position = 0;
+
+    while position < chars.len() {
+        if chars[position].is_whitespace() {
+            position += 1;
+            continue;
+        }
+        let start = position;
+        while position < chars.len() && !chars[position].is_whitespace() {
+            position += 1;
+        }
+        let token: String = chars[start..position].iter().collect();
+        if token.eq_ignore_ascii_case(column) {
+            return Some(ColumnSpan {
+                start: previous_end,
+                end: position,
+                after_index: previous_end == 0 && start > 0,
+            });
+        }
+        previous_end = position;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_luhn() {
+        let digits = |s: &str| s.chars().filter_map(|c| c.to_digit(10)).collect::<Vec<_>>();
+        assert!(passes_luhn(&digits("4111 1111 1111 1111")));
+        assert!(!passes_luhn(&digits("4111 1111 1111 1112")));
+    }
+
+    #[test]
+    fn test_phone_numbers_stay_on_one_line() {
+        let phone = &patterns().phone;
+        assert!(phone.is_match("call 555-123-4567"));
+        assert!(phone.is_match("call (555) 123 4567"));
+        assert!(!phone.is_match("512\n384\n1024\n"));
+        assert!(!phone.is_match("555\n123\n4567\nName: score, dtype: int64"));
+    }
+
+    #[test]
+    fn test_text_table_column() {
+        let scrubber = PiiScrubber::new().with_column("name");
+        let mut state = ScrubState {
+            scrubber: &scrubber,
+            pseudonyms: HashMap::new(),
+            next_id: HashMap::new(),
+            tally: BTreeMap::new(),
+            cell_index: 0,
+        };
+        let table = "    name  age\n0  Alice   31\n1    Bob   42\n2  Alice   27\n";
+        assert_eq!(
+            state.scrub_text(table, false),
+            "    name  age\n0 <NAME_1>   31\n1 <NAME_2>   42\n2 <NAME_1>   27\n"
+        );
+    }
+
+    #[test]
+    fn test_html_table_and_report() {
+        let mut notebook: Notebook = r##"{
+ "cells": [
+  {"cell_type": "code", "execution_count": 1, "metadata": {}, "source": ["notify(customers)"],
+   "outputs": [{"output_type": "stream", "name": "stdout",
+     "text": ["Contacting jane.doe@example.com from 10.0.0.12\n",
+              "Card on file: 4111 1111 1111 1111, call (555) 123-4567\n"]}]},
+  {"cell_type": "code", "execution_count": 2, "metadata": {}, "source": ["customers.head()"],
+   "outputs": [{"output_type": "execute_result", "execution_count": 2, "metadata": {},
+     "data": {
+      "text/plain": ["    name                 email\n", "0  Alice  jane.doe@example.com\n",
+                     "1    Bob       bob@example.org"],
+      "text/html": ["<table><thead><tr><th></th><th>name</th><th>email</th></tr></thead>\n",
+                    "<tbody><tr><th>0</th><td>Alice</td><td>jane.doe@example.com</td></tr>\n",
+                    "<tr><th>1</th><td>Bob</td><td>bob@example.org</td></tr></tbody></table>"]}}]}
+ ],
+ "metadata": {}, "nbformat": 4, "nbformat_minor": 4
+}"##
+        .parse()
+        .unwrap();
+        let report = PiiScrubber::new()
+            .with_column("name")
+            .with_pseudonymize(false)
+            .scrub_notebook(&mut notebook);
+
+        let html = notebook.cells[1].outputs.as_ref().unwrap()[0]
+            .data
+            .as_ref()
+            .unwrap()["text/html"]
+            .to_string();
+        assert!(html.contains("<td><NAME></td><td><EMAIL></td>"));
+        assert!(html.contains("<th>name</th>"));
+        assert_eq!(report.findings[0].cell_index, 0);
+        assert_eq!(report.total(), 12);
+    }
+}
@@ -0,0 +1,968 @@
+//! Lightweight name analysis for Python code cells.
+//!
+//! This is not a full Python parser. The tokenizer understands strings,
+//! comments, brackets and line continuations, which is enough to find the
+//! top-level names a cell imports, assigns, defines and reads. IPython magics
+//! and shell escapes are skipped, and a `name = !cmd` capture counts as an
+//! assignment to `name`.
+
+use crate::Cell;
+use std::collections::BTreeSet;
+
+const KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+    "with", "yield",
+];
+
+/// Cell magics whose body is still Python.
+const PYTHON_CELL_MAGICS: &[&str] = &[
+    "time", "timeit", "capture", "prun", "debug", "python", "python3",
+];
+
+const OPERATORS: &[&str] = &[
+    "**=", "//=", ">>=", "<<=", "...", "==", "!=", "<=", ">=", "->", ":=", "+=", "-=", "*=", "/=",
+    "%=", "&=", "|=", "^=", "@=", "**", "//", "<<", ">>",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Name(String),
+    Op(String),
+    Literal,
+}
+
+impl Token {
+    fn is_op(&self, op: &str) -> bool {
+        matches!(self, Token::Op(value) if value == op)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self, Token::Name(value) if value == keyword)
+    }
+
+    /// Returns the identifier, unless it is a keyword.
+    fn identifier(&self) -> Option<&str> {
+        match self {
+            Token::Name(name) if !KEYWORDS.contains(&name.as_str()) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct LogicalLine {
+    indent: usize,
+    tokens: Vec<Token>,
+}
+
+/// Names a code cell binds and reads at the top level of the kernel namespace.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CellNames {
+    /// Names bound by `import` and `from ... import`.
+    pub imports: BTreeSet<String>,
+    /// Names bound by assignments, `for`, `with` and `except` targets.
+    pub assigned: BTreeSet<String>,
+    /// Functions and classes defined with `def` and `class`.
+    pub defined: BTreeSet<String>,
+    /// Names read before the cell binds them, including builtins.
+    pub referenced: BTreeSet<String>,
+    /// Top-level modules named by import statements anywhere in the cell.
+    /// Relative imports keep their leading dots, as in `.utils`.
+    pub modules: BTreeSet<String>,
+    /// Every name the cell reads, including names it bound itself.
+    pub reads: BTreeSet<String>,
+}
+
+impl CellNames {
+    /// Iterates over every name the cell binds.
+    pub fn bindings(&self) -> impl Iterator<Item = &String> {
+        self.imports
+            .iter()
+            .chain(&self.assigned)
+            .chain(&self.defined)
+    }
+
+    pub fn binds(&self, name: &str) -> bool {
+        self.imports.contains(name) || self.assigned.contains(name) || self.defined.contains(name)
+    }
+}
+
+/// Analyzes the source of a code cell.
+pub fn analyze_cell(cell: &Cell) -> CellNames {
+    let source: Vec<&str> = cell.source_lines().collect();
+    analyze(&source.join("\n"))
+}
+
+/// Analyzes a block of Python source, skipping magics and shell lines.
+pub fn analyze(source: &str) -> CellNames {
+    let Some(source) = strip_magics(source) else {
+        return CellNames::default();
+    };
+    let mut analyzer = Analyzer::default();
+    for line in logical_lines(&source) {
+        analyzer.line(line);
+    }
+    analyzer.finish()
+}
+
+/// Returns true when the source starts with a cell magic whose body is not
+/// Python, such as `%%bash`.
+pub fn is_foreign_cell(source: &str) -> bool {
+    source
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .and_then(|line| line.trim_start().strip_prefix("%%"))
+        .is_some_and(|magic| {
+            let name = magic.split_whitespace().next().unwrap_or("");
+            !PYTHON_CELL_MAGICS.contains(&name)
+        })
+}
+
+/// Returns true for magics, shell escapes, help queries and shell captures
+/// such as `files = !ls`.
+pub fn is_ipython_line(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed.starts_with(['%', '!', '?'])
+        || (trimmed.ends_with('?') && !trimmed.starts_with('#'))
+        || shell_capture_targets(trimmed).is_some()
+}
+
+/// The single-line string literals of a block of source, in order. Comments
+/// are skipped, and so are f-strings and bytes, whose values are not known
+/// or not text.
+pub fn string_literals(source: &str) -> Vec<String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut literals = Vec::new();
+    let mut i = 0;
+    let mut prefix = String::new();
+
+    while i < chars.len() {
+        match chars[i] {
+            '#' => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            quote @ ('"' | '\'') => {
+                let triple = chars.get(i + 1) == Some(&quote) && chars.get(i + 2) == Some(&quote);
+                let (end, content) = read_string(&chars, i);
+                let is_prefix = prefix.len() <= 2 && prefix.chars().all(|c| "rRbBuUfF".contains(c));
+                let formatted = is_prefix && prefix.contains(['f', 'F', 'b', 'B']);
+                if !triple && !formatted {
+                    literals.push(content);
+                }
+                i = end;
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                prefix = chars[start..i].iter().collect();
+                continue;
+            }
+            _ => i += 1,
+        }
+        prefix.clear();
+    }
+    literals
+}
+
+/// Blanks out magics, shell escapes and help queries, keeping line numbers.
+/// Returns `None` for cells written in another language, such as `%%bash`.
+fn strip_magics(source: &str) -> Option<String> {
+    if is_foreign_cell(source) {
+        return None;
+    }
+
+    let lines: Vec<String> = source
+        .lines()
+        .map(|line| {
+            let trimmed = line.trim_start();
+            let indent = &line[..line.len() - trimmed.len()];
+            match shell_capture_targets(trimmed) {
+                Some(targets) => format!("{}{} = None", indent, targets),
+                None if is_ipython_line(line) => String::new(),
+                None => line.to_string(),
+            }
+        })
+        .collect();
+
+    Some(lines.join("\n"))
+}
+
+/// Returns the targets of `files = !ls` or `out = %sx cmd`.
+fn shell_capture_targets(line: &str) -> Option<&str> {
+    let (targets, value) = line.split_once('=')?;
+    let value = value.trim_start();
+    if !(value.starts_with('!') || value.starts_with('%')) || value.starts_with("!=") {
+        return None;
+    }
+    let is_target_list = targets
+        .split(',')
+        .all(|target| is_identifier(target.trim()));
+    is_target_list.then_some(targets.trim_end())
+}
+
+pub(crate) fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Splits source into logical lines of tokens, joining bracketed and
+/// backslash-continued physical lines.
+fn logical_lines(source: &str) -> Vec<LogicalLine> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut lines = Vec::new();
+    let mut tokens = Vec::new();
+    let mut indent = 0;
+    let mut depth = 0usize;
+    let mut at_line_start = true;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if at_line_start {
+            indent = 0;
+            while i < chars.len() && (chars[i] == ' ' || chars[i] == '\t') {
+                indent += if chars[i] == '\t' { 8 - indent % 8 } else { 1 };
+                i += 1;
+            }
+            at_line_start = false;
+            continue;
+        }
+
+        match c {
+            '\n' => {
+                i += 1;
+                if depth == 0 {
+                    if !tokens.is_empty() {
+                        lines.push(LogicalLine {
+                            indent,
+                            tokens: std::mem::take(&mut tokens),
+                        });
+                    }
+                    at_line_start = true;
+                }
+            }
+            '#' => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '\\' if chars.get(i + 1) == Some(&'\n') => i += 2,
+            c if c.is_whitespace() => i += 1,
+            '"' | '\'' => {
+                let (end, _) = read_string(&chars, i);
+                tokens.push(Token::Literal);
+                i = end;
+            }
+            c if c.is_ascii_digit()
+                || (c == '.' && chars.get(i + 1).is_some_and(char::is_ascii_digit)) =>
+            {
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric() || chars[i] == '.' || chars[i] == '_')
+                {
+                    i += 1;
+                }
+                tokens.push(Token::Literal);
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                let is_prefix = word.len() <= 2
+                    && word.chars().all(|c| "rRbBuUfF".contains(c))
+                    && matches!(chars.get(i), Some('"') | Some('\''));
+                if is_prefix {
+                    let (end, content) = read_string(&chars, i);
+                    tokens.push(Token::Literal);
+                    if word.contains(['f', 'F']) {
+                        push_fstring_fields(&content, &mut tokens);
+                    }
+                    i = end;
+                } else {
+                    tokens.push(Token::Name(word));
+                }
+            }
+            _ => {
+                let rest: String = chars[i..chars.len().min(i + 3)].iter().collect();
+                let op = OPERATORS
+                    .iter()
+                    .find(|op| rest.starts_with(**op))
+                    .map(|op| op.to_string())
+                    .unwrap_or_else(|| c.to_string());
+                match op.as_str() {
+                    "(" | "[" | "{" => depth += 1,
+                    ")" | "]" | "}" => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+                i += op.chars().count();
+                tokens.push(Token::Op(op));
+            }
+        }
+    }
+
+    if !tokens.is_empty() {
+        lines.push(LogicalLine { indent, tokens });
+    }
+    lines
+}
+
+/// Reads a string literal starting at its opening quote. Returns the index
+/// after the closing quote and the string content. An unterminated
+/// single-quoted string ends at the line break.
+fn read_string(chars: &[char], start: usize) -> (usize, String) {
+    let quote = chars[start];
+    let triple = chars.get(start + 1) == Some(&quote) && chars.get(start + 2) == Some(&quote);
+    let mut i = start + if triple { 3 } else { 1 };
+    let content_start = i;
+
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            '\n' if !triple => break,
+            c if c == quote => {
+                if !triple {
+                    return (i + 1, chars[content_start..i].iter().collect());
+                }
+                if chars.get(i + 1) == Some(&quote) && chars.get(i + 2) == Some(&quote) {
+                    return (i + 3, chars[content_start..i].iter().collect());
+                }
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+
+    let end = i.min(chars.len());
+    (end, chars[content_start..end].iter().collect())
+}
+
+/// Tokenizes the replacement fields of an f-string as a parenthesized
+/// expression so the names they read count as references.
+fn push_fstring_fields(content: &str, tokens: &mut Vec<Token>) {
+    let chars: Vec<char> = content.chars().collect();
+    let mut i = 0;
+
+    while i < chars.len() {
+        if chars[i] != '{' {
+            i += 1;
+            continue;
+        }
+        if chars.get(i + 1) == Some(&'{') {
+            i += 2;
+            continue;
+        }
+
+        let start = i + 1;
+        let mut depth = 0;
+        let mut end = start;
+        while end < chars.len() {
+            match chars[end] {
+                '{' | '(' | '[' => depth += 1,
+                ')' | ']' => depth -= 1,
+                '}' if depth == 0 => break,
+                '}' => depth -= 1,
+                ':' if depth == 0 => break,
+                '!' if depth == 0 && chars.get(end + 1) != Some(&'=') => break,
+                _ => {}
+            }
+            end += 1;
+        }
+
+        let expression: String = chars[start..end].iter().collect();
+        tokens.push(Token::Op("(".to_string()));
+        for line in logical_lines(&expression) {
+            tokens.extend(line.tokens);
+        }
+        tokens.push(Token::Op(")".to_string()));
+
+        // Skip the conversion and format spec, which may nest fields.
+        let mut depth = 0;
+        i = end;
+        while i < chars.len() {
+            match chars[i] {
+                '{' => depth += 1,
+                '}' if depth == 0 => break,
+                '}' => depth -= 1,
+                _ => {}
+            }
+            i += 1;
+        }
+        i += 1;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScopeKind {
+    Function,
+    Class,
+}
+
+/// A `def` or `class` body. Names read inside it are resolved against its
+/// locals once the whole body has been seen.
+#[derive(Debug)]
+struct Scope {
+    kind: ScopeKind,
+    indent: usize,
+    locals: BTreeSet<String>,
+    globals: BTreeSet<String>,
+    references: Vec<String>,
+    /// Free names of methods, which do not see the class namespace.
+    passthrough: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Binding {
+    Import,
+    Assignment,
+    Definition,
+}
+
+#[derive(Debug, Default)]
+struct Analyzer {
+    names: CellNames,
+    scopes: Vec<Scope>,
+    /// Free names of function and class bodies, read when they are called.
+    deferred: Vec<String>,
+}
+
+impl Analyzer {
+    fn finish(mut self) -> CellNames {
+        while !self.scopes.is_empty() {
+            self.pop_scope();
+        }
+        for name in std::mem::take(&mut self.deferred) {
+            if !self.names.binds(&name) {
+                self.names.referenced.insert(name);
+            }
+        }
+        self.names
+    }
+
+    fn line(&mut self, line: LogicalLine) {
+        while self
+            .scopes
+            .last()
+            .is_some_and(|scope| scope.indent >= line.indent)
+        {
+            self.pop_scope();
+        }
+        for statement in split_top_level(&line.tokens, ";") {
+            self.statement(statement, line.indent);
+        }
+    }
+
+    fn pop_scope(&mut self) {
+        let Some(scope) = self.scopes.pop() else {
+            return;
+        };
+        let free = scope
+            .references
+            .into_iter()
+            .filter(|name| !scope.locals.contains(name))
+            .chain(scope.passthrough);
+
+        match self.scopes.last_mut() {
+            Some(parent)
+                if scope.kind == ScopeKind::Function && parent.kind == ScopeKind::Class =>
+            {
+                parent.passthrough.extend(free)
+            }
+            Some(parent) => parent.references.extend(free),
+            None => self.deferred.extend(free),
+        }
+    }
+
+    fn bind(&mut self, name: &str, binding: Binding) {
+        if let Some(scope) = self.scopes.last_mut() {
+            if !scope.globals.contains(name) {
+                scope.locals.insert(name.to_string());
+                return;
+            }
+        }
+        let names = match binding {
+            Binding::Import => &mut self.names.imports,
+            Binding::Assignment => &mut self.names.assigned,
+            Binding::Definition => &mut self.names.defined,
+        };
+        names.insert(name.to_string());
+    }
+
+    fn reference(&mut self, name: &str) {
+        self.names.reads.insert(name.to_string());
+        match self.scopes.last_mut() {
+            Some(scope) => scope.references.push(name.to_string()),
+            None => {
+                if !self.names.binds(name) {
+                    self.names.referenced.insert(name.to_string());
+                }
+            }
+        }
+    }
+
+    fn references(&mut self, tokens: &[Token]) {
+        for name in expression_names(tokens) {
+            self.reference(&name);
+        }
+        for name in walrus_targets(tokens) {
+            self.bind(&name, Binding::Assignment);
+        }
+    }
+
+    fn statement(&mut self, mut tokens: &[Token], indent: usize) {
+        if tokens.first().is_some_and(|token| token.is_op("@")) {
+            self.references(&tokens[1..]);
+            return;
+        }
+        if tokens
+            .first()
+            .is_some_and(|token| token.is_keyword("async"))
+        {
+            tokens = &tokens[1..];
+        }
+        let Some(Token::Name(keyword)) = tokens.first() else {
+            self.simple_statement(tokens);
+            return;
+        };
+
+        match keyword.as_str() {
+            "import" => {
+                for alias in split_top_level(&tokens[1..], ",") {
+                    if let Some(module) = alias.first().and_then(Token::identifier) {
+                        self.names.modules.insert(module.to_string());
+                    }
+                    let bound = match alias.iter().position(|token| token.is_keyword("as")) {
+                        Some(position) => alias.get(position + 1),
+                        None => alias.first(),
+                    };
+                    if let Some(name) = bound.and_then(Token::identifier) {
+                        self.bind(name, Binding::Import);
+                    }
+                }
+            }
+            "from" => {
+                let Some(position) = tokens.iter().position(|token| token.is_keyword("import"))
+                else {
+                    return;
+                };
+                let relative = tokens
+                    .get(1)
+                    .is_some_and(|token| token.is_op(".") || token.is_op("..."));
+                let module = if relative {
+                    tokens[1..position]
+                        .iter()
+                        .map(|token| match token {
+                            Token::Name(text) | Token::Op(text) => text.as_str(),
+                            Token::Literal => "",
+                        })
+                        .collect()
+                } else {
+                    tokens
+                        .get(1)
+                        .and_then(Token::identifier)
+                        .unwrap_or_default()
+                        .to_string()
+                };
+                if !module.is_empty() {
+                    self.names.modules.insert(module);
+                }
+                let names: Vec<&Token> = tokens[position + 1..]
+                    .iter()
+                    .filter(|token| !token.is_op("(") && !token.is_op(")"))
+                    .collect();
+                for alias in names.split(|token| token.is_op(",")) {
+                    let bound = match alias.iter().position(|token| token.is_keyword("as")) {
+                        Some(position) => alias.get(position + 1),
+                        None => alias.first(),
+                    };
+                    if let Some(name) = bound.and_then(|token| token.identifier()) {
+                        self.bind(name, Binding::Import);
+                    }
+                }
+            }
+            "global" => {
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.globals.extend(
+                        tokens[1..]
+                            .iter()
+                            .filter_map(Token::identifier)
+                            .map(str::to_string),
+                    );
+                }
+            }
+            "nonlocal" | "pass" | "break" | "continue" => {}
+            "def" | "class" => self.definition(tokens, indent),
+            "for" => {
+                let (header, body) = split_header(tokens);
+                let in_position = header
+                    .iter()
+                    .position(|token| token.is_keyword("in"))
+                    .unwrap_or(header.len());
+                self.references(&header[in_position.min(header.len())..]);
+                self.targets(&header[1..in_position]);
+                self.inline_body(body, indent);
+            }
+            "with" | "except" => {
+                let (header, body) = split_header(tokens);
+                for item in split_top_level(&header[1..], ",") {
+                    match item.iter().position(|token| token.is_keyword("as")) {
+                        Some(position) => {
+                            self.references(&item[..position]);
+                            self.targets(&item[position + 1..]);
+                        }
+                        None => self.references(item),
+                    }
+                }
+                self.inline_body(body, indent);
+            }
+            "if" | "elif" | "while" | "else" | "try" | "finally" => {
+                let (header, body) = split_header(tokens);
+                self.references(&header[1..]);
+                self.inline_body(body, indent);
+            }
+            "return" | "yield" | "raise" | "assert" | "del" | "await" => {
+                self.references(&tokens[1..]);
+            }
+            _ => self.simple_statement(tokens),
+        }
+    }
+
+    fn inline_body(&mut self, body: &[Token], indent: usize) {
+        if !body.is_empty() {
+            self.statement(body, indent);
+        }
+    }
+
+    fn definition(&mut self, tokens: &[Token], indent: usize) {
+        let kind = if tokens[0].is_keyword("def") {
+            ScopeKind::Function
+        } else {
+            ScopeKind::Class
+        };
+        let Some(name) = tokens.get(1).and_then(Token::identifier) else {
+            return;
+        };
+        let name = name.to_string();
+        let (header, body) = split_header(tokens);
+
+        // Defaults, annotations and base classes are read where the
+        // definition runs; parameters become locals of the body.
+        let mut parameters = BTreeSet::new();
+        let mut header_reads = Vec::new();
+        let mut depth = 0;
+        for (position, token) in header.iter().enumerate().skip(2) {
+            match token {
+                Token::Op(op) if op == "(" || op == "[" || op == "{" => depth += 1,
+                Token::Op(op) if op == ")" || op == "]" || op == "}" => depth -= 1,
+                _ => {}
+            }
+            let Some(identifier) = token.identifier() else {
+                continue;
+            };
+            let previous = &header[position - 1];
+            let starts_parameter = depth == 1
+                && kind == ScopeKind::Function
+                && ["(", ",", "*", "**"].iter().any(|op| previous.is_op(op));
+            if starts_parameter {
+                parameters.insert(identifier.to_string());
+            } else if !previous.is_op(".")
+                && !header
+                    .get(position + 1)
+                    .is_some_and(|next| next.is_op("=") && depth == 1)
+            {
+                header_reads.push(identifier.to_string());
+            }
+        }
+        for read in header_reads {
+            self.reference(&read);
+        }
+
+        self.bind(&name, Binding::Definition);
+        self.scopes.push(Scope {
+            kind,
+            indent,
+            locals: parameters,
+            globals: BTreeSet::new(),
+            references: Vec::new(),
+            passthrough: Vec::new(),
+        });
+        self.inline_body(body, indent + 1);
+    }
+
+    fn simple_statement(&mut self, tokens: &[Token]) {
+        if let Some(position) = tokens.iter().position(|token| {
+            matches!(token, Token::Op(op) if op.len() >= 2 && op.ends_with('=') && !["==", "!=", "<=", ">=", ":="].contains(&op.as_str()))
+        }) {
+            // Augmented assignment reads the target before rebinding it.
+            self.references(&tokens[position + 1..]);
+            self.references(&tokens[..position]);
+            self.targets(&tokens[..position]);
+            return;
+        }
+
+        let parts = split_top_level(tokens, "=");
+        let (value, targets) = parts.split_last().expect("split yields at least one part");
+        let annotation = targets
+            .is_empty()
+            .then(|| split_top_level(value, ":"))
+            .filter(|parts| parts.len() == 2);
+
+        if let Some(parts) = annotation {
+            // `name: Type` without a value is a bare annotation.
+            self.references(parts[1]);
+            return;
+        }
+
+        self.references(value);
+        for target in targets {
+            let target = match split_top_level(target, ":").as_slice() {
+                [target, annotation] => {
+                    self.references(annotation);
+                    *target
+                }
+                _ => target,
+            };
+            self.targets(target);
+        }
+    }
+
+    /// Binds the names of an assignment target. Attribute and subscript
+    /// targets read their base instead.
+    fn targets(&mut self, tokens: &[Token]) {
+        let mut subscript_depth = 0;
+        let mut binds = Vec::new();
+        let mut reads = Vec::new();
+
+        for (position, token) in tokens.iter().enumerate() {
+            let previous = position.checked_sub(1).map(|position| &tokens[position]);
+            match token {
+                Token::Op(op) if op == "[" || op == "(" => {
+                    let is_subscript = subscript_depth > 0
+                        || previous.is_some_and(|previous| {
+                            previous.identifier().is_some()
+                                || previous.is_op(")")
+                                || previous.is_op("]")
+                        });
+                    if is_subscript {
+                        subscript_depth += 1;
+                    }
+                }
+                Token::Op(op) if (op == "]" || op == ")") && subscript_depth > 0 => {
+                    subscript_depth -= 1
+                }
+                _ => {}
+            }
+            let Some(name) = token.identifier() else {
+                continue;
+            };
+            if previous.is_some_and(|previous| previous.is_op(".")) {
+                continue;
+            }
+            let next = tokens.get(position + 1);
+            let is_base =
+                next.is_some_and(|next| next.is_op(".") || next.is_op("[") || next.is_op("("));
+            if subscript_depth > 0 || is_base {
+                reads.push(name.to_string());
+            } else {
+                binds.push(name.to_string());
+            }
+        }
+
+        for name in reads {
+            self.reference(&name);
+        }
+        for name in binds {
+            self.bind(&name, Binding::Assignment);
+        }
+    }
+}
+
+/// Splits tokens on a separator that is outside brackets and, for `:`,
+/// outside lambdas.
+fn split_top_level<'a>(tokens: &'a [Token], separator: &str) -> Vec<&'a [Token]> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut pending_lambdas = 0;
+    let mut start = 0;
+
+    for (position, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Op(op) if op == "(" || op == "[" || op == "{" => depth += 1,
+            Token::Op(op) if op == ")" || op == "]" || op == "}" => depth -= 1,
+            Token::Name(name) if name == "lambda" && depth == 0 => pending_lambdas += 1,
+            Token::Op(op) if op == ":" && depth == 0 && pending_lambdas > 0 => {
+                pending_lambdas -= 1;
+                continue;
+            }
+            _ => {}
+        }
+        if depth == 0 && token.is_op(separator) {
+            parts.push(&tokens[start..position]);
+            start = position + 1;
+        }
+    }
+
+    parts.push(&tokens[start..]);
+    parts
+}
+
+/// Splits a compound statement into its header and the body written on the
+/// same line, if any.
+fn split_header(tokens: &[Token]) -> (&[Token], &[Token]) {
+    let parts = split_top_level(tokens, ":");
+    let header = parts[0];
+    let body_start = (header.len() + 1).min(tokens.len());
+    (header, &tokens[body_start..])
+}
+
+/// Returns the names an expression reads. Attributes, keyword argument
+/// names, lambda parameters and comprehension variables are left out.
+fn expression_names(tokens: &[Token]) -> Vec<String> {
+    let mut local = BTreeSet::new();
+    for (position, token) in tokens.iter().enumerate() {
+        if token.is_keyword("lambda") {
+            for parameter in tokens[position + 1..]
+                .iter()
+                .take_while(|token| !token.is_op(":"))
+            {
+                if let Some(name) = parameter.identifier() {
+                    local.insert(name.to_string());
+                }
+            }
+        }
+        if token.is_keyword("for") {
+            for target in tokens[position + 1..]
+                .iter()
+                .take_while(|token| !token.is_keyword("in"))
+            {
+                if let Some(name) = target.identifier() {
+                    local.insert(name.to_string());
+                }
+            }
+        }
+    }
+
+    let mut names = Vec::new();
+    let mut depth = 0;
+    for (position, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Op(op) if op == "(" || op == "[" || op == "{" => depth += 1,
+            Token::Op(op) if op == ")" || op == "]" || op == "}" => depth -= 1,
+            _ => {}
+        }
+        let Some(name) = token.identifier() else {
+            continue;
+        };
+        let after_dot = position > 0 && tokens[position - 1].is_op(".");
+        let keyword_argument =
+            depth > 0 && tokens.get(position + 1).is_some_and(|next| next.is_op("="));
+        let walrus_target = tokens
+            .get(position + 1)
+            .is_some_and(|next| next.is_op(":="));
+        if !after_dot && !keyword_argument && !walrus_target && !local.contains(name) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+fn walrus_targets(tokens: &[Token]) -> Vec<String> {
+    tokens
+        .windows(2)
+        .filter(|pair| pair[1].is_op(":="))
+        .filter_map(|pair| pair[0].identifier().map(str::to_string))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(names: &[&str]) -> BTreeSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_analyze_bindings_and_references() {
+        let names = analyze(
+            "import numpy as np, os.path\n\
+             from sklearn.metrics import (accuracy_score as acc, f1_score)\n\
+             %matplotlib inline\n\
+             !pip install torch\n\
+             files = !ls data\n\
+             df = load(path)  # comment with unused_name\n\
+             df.columns = [c.lower() for c in df.columns]\n\
+             total += len(df)\n\
+             label = f\"{prefix!r}: {acc(y, yhat):.2f}\"\n\
+             model.fit(X, epochs=epochs)\n",
+        );
+
+        assert_eq!(names.imports, set(&["acc", "f1_score", "np", "os"]));
+        assert_eq!(names.modules, set(&["numpy", "os", "sklearn"]));
+        assert_eq!(names.assigned, set(&["df", "files", "label", "total"]));
+        assert_eq!(
+            names.referenced,
+            set(&["X", "epochs", "len", "load", "model", "path", "prefix", "total", "y", "yhat"])
+        );
+    }
+
+    #[test]
+    fn test_string_literals() {
+        assert_eq!(
+            string_literals(
+                "df = pd.read_csv(\"data/sales.csv\", sep=';')  # or \"old.csv\"\n\
+                 raw = open(r'C:\\data.json').read() + f\"{name}.csv\" + b'x'\n\
+                 \"\"\"Docstring.\"\"\"\n"
+            ),
+            vec!["data/sales.csv", ";", "C:\\data.json"]
+        );
+    }
+
+    #[test]
+    fn test_analyze_function_scopes() {
+        let names = analyze(
+            "@register(registry)\n\
+             def train(model, lr=default_lr, *args, **kwargs):\n    \
+                 global step\n    \
+                 step = 0\n    \
+                 loss = criterion(model(batch))\n    \
+                 return helper(loss)\n\
+             \n\
+             class Trainer(Base):\n    \
+                 epochs = 3\n    \
+                 def run(self):\n        \
+                     return epochs + train(self)\n\
+             \n\
+             def helper(value):\n    \
+                 return value\n\
+             \n\
+             %%time\n\
+             result = (lambda x: x * scale)(2)\n",
+        );
+
+        assert_eq!(names.defined, set(&["Trainer", "helper", "train"]));
+        assert_eq!(names.assigned, set(&["result", "step"]));
+        assert_eq!(
+            names.referenced,
+            set(&[
+                "Base",
+                "batch",
+                "criterion",
+                "default_lr",
+                "epochs",
+                "register",
+                "registry",
+                "scale"
+            ])
+        );
+        assert_eq!(analyze("%%bash\necho $HOME"), CellNames::default());
+    }
+}
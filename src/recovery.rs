@@ -0,0 +1,289 @@
+//! Lenient parsing for notebooks that the strict parser rejects.
+//!
+//! One broken cell should not cost the whole notebook when converting
+//! thousands of scraped files. `parse_lenient` turns cells that do not
+//! deserialize into placeholders, drops broken outputs, accepts single-string
+//! sources and cuts a truncated file back to its last complete value.
+//! Everything it had to change is reported as a diagnostic.
+
+use crate::diagnostic::Diagnostic;
+use crate::location::{self, ErrorLocation};
+use crate::{Cell, JupyterError, Notebook, Output};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// Cell type of the placeholder that stands in for a cell that could not be
+/// read. Its source holds the reason.
+pub const UNREADABLE_CELL_TYPE: &str = "unreadable";
+
+/// Parses a notebook, recovering from malformed cells and truncated JSON.
+///
+/// Only content that is not a notebook at all, such as invalid JSON that is
+/// not merely cut short or a document that is not an object, is an error.
+pub fn parse_lenient(content: &str) -> Result<(Notebook, Vec<Diagnostic>), JupyterError> {
+    let mut diagnostics = Vec::new();
+
+    let value = match serde_json::from_str::<Value>(content) {
+        Ok(value) => value,
+        Err(err) if err.is_eof() => {
+            let Some((value, kept)) = salvage_truncated(content) else {
+                return Err(location::parse_error(err, content));
+            };
+            diagnostics.push(Diagnostic::warning(
+                None,
+                "truncated-json",
+                format!(
+                    "the notebook JSON is truncated, recovered the first {} of {} bytes",
+                    kept,
+                    content.len()
+                ),
+            ));
+            value
+        }
+        Err(err) => return Err(location::parse_error(err, content)),
+    };
+
+    let Value::Object(mut root) = value else {
+        return Err(JupyterError::InvalidNotebookFormat {
+            message: "notebook must be a JSON object".to_string(),
+            location: None,
+        });
+    };
+
+    let nbformat = match root.get("nbformat").and_then(Value::as_u64) {
+        Some(4) => 4,
+        Some(version) => {
+            diagnostics.push(Diagnostic::note(
+                None,
+                "upgrade",
+                format!("read nbformat {} as nbformat 4", version),
+            ));
+            version as u32
+        }
+        None => {
+            diagnostics.push(Diagnostic::note(
+                None,
+                "upgrade",
+                "missing nbformat, assuming 4",
+            ));
+            4
+        }
+    };
+    let nbformat_minor = root
+        .get("nbformat_minor")
+        .and_then(Value::as_u64)
+        .unwrap_or(0) as u32;
+
+    let metadata = match root.remove("metadata") {
+        Some(Value::Object(metadata)) => metadata.into_iter().collect(),
+        Some(_) => {
+            diagnostics.push(Diagnostic::warning(
+                None,
+                "invalid-metadata",
+                "notebook metadata is not an object, dropped",
+            ));
+            HashMap::new()
+        }
+        None => HashMap::new(),
+    };
+
+    let cells = match root.remove("cells") {
+        Some(Value::Array(cells)) => cells,
+        Some(_) => {
+            return Err(JupyterError::InvalidNotebookFormat {
+                message: "cells must be an array".to_string(),
+                location: Some(Box::new(ErrorLocation::at_pointer(content, "/cells"))),
+            })
+        }
+        None => {
+            diagnostics.push(Diagnostic::warning(
+                None,
+                "missing-cells",
+                "missing cells, the notebook is empty",
+            ));
+            Vec::new()
+        }
+    };
+    let cells = cells
+        .into_iter()
+        .enumerate()
+        .map(|(index, cell)| recover_cell(index, cell, &mut diagnostics))
+        .collect();
+
+    let notebook = Notebook {
+        cells,
+        metadata,
+        nbformat,
+        nbformat_minor,
+    };
+    Ok((notebook, diagnostics))
+}
+
+fn recover_cell(index: usize, value: Value, diagnostics: &mut Vec<Diagnostic>) -> Cell {
+    let Value::Object(mut fields) = value else {
+        return placeholder(index, "cell is not a JSON object".to_string(), diagnostics);
+    };
+
+    if let Some(source) = fields.get_mut("source") {
+        if split_multiline(source) {
+            diagnostics.push(Diagnostic::note(
+                Some(index),
+                "upgrade",
+                "split the single-string source into lines",
+            ));
+        }
+    }
+    if !fields.get("metadata").is_some_and(Value::is_object) {
+        fields.insert("metadata".to_string(), Value::Object(Map::new()));
+    }
+    let outputs = fields.remove("outputs");
+
+    let mut cell: Cell = match serde_json::from_value(Value::Object(fields)) {
+        Ok(cell) => cell,
+        Err(err) => return placeholder(index, err.to_string(), diagnostics),
+    };
+
+    match outputs {
+        Some(Value::Array(outputs)) => {
+            let outputs = outputs
+                .into_iter()
+                .enumerate()
+                .filter_map(|(number, mut output)| {
+                    if let Some(text) = output.get_mut("text") {
+                        split_multiline(text);
+                    }
+                    serde_json::from_value::<Output>(output)
+                        .map_err(|err| {
+                            diagnostics.push(Diagnostic::warning(
+                                Some(index),
+                                "dropped-output",
+                                format!("dropped output {}: {}", number + 1, err),
+                            ))
+                        })
+                        .ok()
+                })
+                .collect();
+            cell.outputs = Some(outputs);
+        }
+        Some(Value::Null) | None => {}
+        Some(_) => diagnostics.push(Diagnostic::warning(
+            Some(index),
+            "dropped-output",
+            "outputs is not an array, dropped",
+        )),
+    }
+
+    cell
+}
+
+fn placeholder(index: usize, reason: String, diagnostics: &mut Vec<Diagnostic>) -> Cell {
+    diagnostics.push(Diagnostic::warning(
+        Some(index),
+        "unreadable-cell",
+        format!("{}, rendered as a placeholder", reason),
+    ));
+    Cell {
+        cell_type: UNREADABLE_CELL_TYPE.to_string(),
+        source: vec![reason],
+        metadata: HashMap::new(),
+        outputs: None,
+        execution_count: None,
+    }
+}
+
+/// nbformat allows a multiline string to be stored as one string; the model
+/// expects the list form. Returns true if the value was split.
+fn split_multiline(value: &mut Value) -> bool {
+    let Value::String(text) = value else {
+        return false;
+    };
+    let lines = text
+        .split_inclusive('\n')
+        .map(|line| Value::String(line.to_string()))
+        .collect();
+    *value = Value::Array(lines);
+    true
+}
+
+/// Cuts a truncated document back to its last complete object or array
+/// value and closes the containers that are still open. Returns the parsed
+/// value and the number of bytes kept.
+pub(crate) fn salvage_truncated(content: &str) -> Option<(Value, usize)> {
+    let mut open = Vec::new();
+    let mut candidates = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (position, c) in content.char_indices() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' => open.push('}'),
+            '[' => open.push(']'),
+            '}' | ']' => {
+                open.pop();
+                if !open.is_empty() {
+                    let closers: String = open.iter().rev().collect();
+                    candidates.push((position + 1, closers));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    candidates.iter().rev().find_map(|(end, closers)| {
+        let repaired = format!("{}{}", &content[..*end], closers);
+        serde_json::from_str(&repaired)
+            .ok()
+            .map(|value| (value, *end))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_salvage_truncated() {
+        let content = r#"{"cells": [{"cell_type": "code", "source": ["a = \"]}\""]}, {"cell_ty"#;
+        let (value, kept) = salvage_truncated(content).unwrap();
+        assert_eq!(kept, content.find("}, {").unwrap() + 1);
+        assert_eq!(value["cells"].as_array().unwrap().len(), 1);
+        assert_eq!(value["cells"][0]["source"][0], "a = \"]}\"");
+
+        assert!(salvage_truncated(r#"{"cells": ["#).is_none());
+    }
+
+    #[test]
+    fn test_split_multiline() {
+        let mut value = Value::String("a = 1\nb = 2".to_string());
+        assert!(split_multiline(&mut value));
+        assert_eq!(value, serde_json::json!(["a = 1\n", "b = 2"]));
+        assert!(!split_multiline(&mut value));
+    }
+
+    #[test]
+    fn test_parse_truncated_notebook() {
+        let truncated = r##"{
+ "cells": [
+  {"cell_type": "markdown", "metadata": {}, "source": ["# Test Notebook"]},
+  {"cell_type": "code", "execution_count": 1, "metadata": {}, "outputs": [], "source": ["##;
+        assert!(truncated.parse::<Notebook>().is_err());
+
+        let (notebook, diagnostics) = parse_lenient(truncated).unwrap();
+        assert_eq!(notebook.cells.len(), 2);
+        assert_eq!(notebook.cells[0].source, ["# Test Notebook"]);
+        assert_eq!(notebook.cells[1].cell_type, UNREADABLE_CELL_TYPE);
+        assert_eq!(diagnostics[0].code, "truncated-json");
+        assert_eq!(diagnostics[1].message, "missing nbformat, assuming 4");
+    }
+}
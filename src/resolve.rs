@@ -0,0 +1,568 @@
+//! Local files a notebook pulls in.
+//!
+//! Notebooks often `import utils` or `%run ./prep.ipynb` from their own
+//! directory, which leaves a model guessing what those files contain. The
+//! resolver finds imported modules and `%run`/`%load` targets relative to
+//! the referencing file, follows their own references up to a depth and a
+//! size budget, and renders them as an appendix. Referenced notebooks are
+//! converted with the same converter; Python files are shown as source,
+//! redacted and anonymized like the notebook. A file that refers back to one
+//! of the files that led to it is reported as a cycle and not followed.
+//! Targets outside the notebook's directory, such as absolute paths or
+//! `../` paths, are never read: a scraped notebook could otherwise pull any
+//! local file into the output.
+
+use crate::bundle;
+use crate::diagnostic::Diagnostic;
+use crate::{python, recovery, JupyterConverter, JupyterError, Notebook};
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+/// How many references deep the resolver follows by default.
+pub const DEFAULT_MAX_DEPTH: usize = 2;
+/// Default budget for the combined size of the referenced files.
+pub const DEFAULT_MAX_BYTES: u64 = 256 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReferenceLimits {
+    /// References of the notebook are depth 1, theirs depth 2 and so on.
+    pub max_depth: usize,
+    pub max_bytes: u64,
+}
+
+impl Default for ReferenceLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_bytes: DEFAULT_MAX_BYTES,
+        }
+    }
+}
+
+/// The statement that refers to a file, with the module or path as written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReferenceKind {
+    Import(String),
+    Run(String),
+    Load(String),
+}
+
+impl fmt::Display for ReferenceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReferenceKind::Import(module) => write!(f, "import {}", module),
+            ReferenceKind::Run(target) => write!(f, "%run {}", target),
+            ReferenceKind::Load(target) => write!(f, "%load {}", target),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    pub kind: ReferenceKind,
+    /// The file that contains the reference.
+    pub from: PathBuf,
+    /// Zero-based cell of the reference when `from` is a notebook.
+    pub cell_index: Option<usize>,
+    pub target: PathBuf,
+}
+
+impl fmt::Display for Reference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}`", self.kind)?;
+        if let Some(index) = self.cell_index {
+            write!(f, " in cell {} of", index + 1)?;
+        } else {
+            write!(f, " in")?;
+        }
+        write!(f, " {}", self.from.display())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedFile {
+    pub reference: Reference,
+    pub depth: usize,
+    /// The converted notebook or the fenced source.
+    pub text: String,
+}
+
+/// The referenced files in the order they were found, depth first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Resolution {
+    pub files: Vec<ResolvedFile>,
+    /// Cycles, files left out and redactions, with the cells of the
+    /// notebook they come from.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl fmt::Display for Resolution {
+    /// The appendix with one delimited section per file, or nothing.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.files.is_empty() {
+            return Ok(());
+        }
+        write!(f, "# Referenced Files\n\n")?;
+        for file in &self.files {
+            let text = format!("*Referenced by {}*\n\n{}", file.reference, file.text);
+            writeln!(f, "{}", bundle::file_section(&file.reference.target, &text))?;
+        }
+        Ok(())
+    }
+}
+
+/// Resolves the references of `notebook`, loaded from `path`.
+pub fn resolve(
+    converter: &JupyterConverter,
+    path: &Path,
+    notebook: &Notebook,
+    limits: ReferenceLimits,
+) -> Resolution {
+    let root = canonical(path);
+    let mut resolver = Resolver {
+        converter,
+        limits,
+        dir: root.parent().unwrap_or(Path::new("")).to_path_buf(),
+        root: root.clone(),
+        seen: HashSet::from([root.clone()]),
+        too_deep: Vec::new(),
+        used_bytes: 0,
+        resolution: Resolution::default(),
+    };
+    let references = notebook_references(path, notebook);
+    resolver.visit(references, 1, &mut vec![root]);
+    resolver.report_too_deep();
+    resolver
+        .resolution
+        .diagnostics
+        .sort_by_key(|diagnostic| diagnostic.cell_index);
+    resolver.resolution
+}
+
+struct Resolver<'a> {
+    converter: &'a JupyterConverter,
+    limits: ReferenceLimits,
+    root: PathBuf,
+    /// The directory of the notebook, which references may not leave.
+    dir: PathBuf,
+    /// Files that were included or can never be.
+    seen: HashSet<PathBuf>,
+    /// References beyond the depth limit. The file may still be included
+    /// through a shorter path found later.
+    too_deep: Vec<(PathBuf, Reference)>,
+    used_bytes: u64,
+    resolution: Resolution,
+}
+
+impl Resolver<'_> {
+    fn visit(&mut self, references: Vec<Reference>, depth: usize, chain: &mut Vec<PathBuf>) {
+        for reference in references {
+            let key = canonical(&reference.target);
+            if chain.contains(&key) {
+                let message = format!(
+                    "{} refers back to {}, not followed",
+                    reference,
+                    reference.target.display()
+                );
+                self.note(&reference, "reference-cycle", message);
+                continue;
+            }
+            if self.seen.contains(&key) {
+                continue;
+            }
+            if !is_within(&self.dir, &reference.target) {
+                self.seen.insert(key);
+                let message = format!(
+                    "{} not included, {} is outside the notebook's directory",
+                    reference,
+                    reference.target.display()
+                );
+                self.warn(&reference, message);
+                continue;
+            }
+            if depth > self.limits.max_depth {
+                self.too_deep.push((key, reference));
+                continue;
+            }
+            self.seen.insert(key.clone());
+
+            let content = match std::fs::read_to_string(&reference.target) {
+                Ok(content) => content,
+                Err(err) => {
+                    self.warn(
+                        &reference,
+                        format!("{} could not be read: {}", reference, err),
+                    );
+                    continue;
+                }
+            };
+            let size = content.len() as u64;
+            if self.used_bytes + size > self.limits.max_bytes {
+                let message = format!(
+                    "{} not included, {} bytes would exceed the {}-byte reference limit",
+                    reference, size, self.limits.max_bytes
+                );
+                self.warn(&reference, message);
+                continue;
+            }
+
+            let Some(text) = self.render(&reference, &content) else {
+                continue;
+            };
+            self.used_bytes += size;
+            let nested = match find_references(&reference.target, &content) {
+                Ok(nested) => nested,
+                Err(err) => {
+                    self.warn(&reference, format!("{}: {}", reference, err));
+                    Vec::new()
+                }
+            };
+            self.resolution.files.push(ResolvedFile {
+                reference,
+                depth,
+                text,
+            });
+            chain.push(key);
+            self.visit(nested, depth + 1, chain);
+            chain.pop();
+        }
+    }
+
+    /// Reports the references beyond the depth limit whose files were not
+    /// included through another reference, once per file.
+    fn report_too_deep(&mut self) {
+        for (key, reference) in std::mem::take(&mut self.too_deep) {
+            if self.seen.insert(key) {
+                let message = format!(
+                    "{} not included, beyond the reference depth of {}",
+                    reference, self.limits.max_depth
+                );
+                self.warn(&reference, message);
+            }
+        }
+    }
+
+    /// Converts a notebook, or fences a Python file after redacting and
+    /// anonymizing it.
+    fn render(&mut self, reference: &Reference, content: &str) -> Option<String> {
+        if is_notebook(&reference.target) {
+            return match self.converter.convert_str(content) {
+                Ok(conversion) => Some(conversion.text.trim_end().to_string()),
+                Err(err) => {
+                    self.warn(
+                        reference,
+                        format!("{} could not be converted: {}", reference, err),
+                    );
+                    None
+                }
+            };
+        }
+
+        let mut source = content.trim_end().to_string();
+        for (rule, count) in self.converter.filter_source(&mut source) {
+            let message = format!(
+                "replaced {} {} match(es) in {}",
+                count,
+                rule,
+                reference.target.display()
+            );
+            self.note(reference, "redacted", message);
+        }
+        Some(format!("```python\n{}\n```", source))
+    }
+
+    /// Diagnostics name a cell only for references made by the notebook
+    /// being converted.
+    fn cell_of(&self, reference: &Reference) -> Option<usize> {
+        reference
+            .cell_index
+            .filter(|_| canonical(&reference.from) == self.root)
+    }
+
+    fn note(&mut self, reference: &Reference, code: &'static str, message: String) {
+        let cell = self.cell_of(reference);
+        self.resolution
+            .diagnostics
+            .push(Diagnostic::note(cell, code, message));
+    }
+
+    fn warn(&mut self, reference: &Reference, message: String) {
+        let cell = self.cell_of(reference);
+        self.resolution
+            .diagnostics
+            .push(Diagnostic::warning(cell, "reference-skipped", message));
+    }
+}
+
+/// Finds the local files a notebook or Python file refers to. Imports that
+/// do not name a file next to it, such as installed packages, are ignored.
+pub fn find_references(path: &Path, content: &str) -> Result<Vec<Reference>, JupyterError> {
+    if is_notebook(path) {
+        let (notebook, _) = recovery::parse_lenient(content)?;
+        return Ok(notebook_references(path, &notebook));
+    }
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut references = Vec::new();
+    for module in python::analyze(content).modules {
+        let target = module_path(dir, &module);
+        add_reference(
+            &mut references,
+            path,
+            ReferenceKind::Import(module),
+            None,
+            target,
+        );
+    }
+    Ok(references)
+}
+
+/// Finds the local files the cells of `notebook`, loaded from `path`, refer
+/// to.
+pub fn notebook_references(path: &Path, notebook: &Notebook) -> Vec<Reference> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut references = Vec::new();
+    for (index, cell) in notebook.cells.iter().enumerate() {
+        if cell.cell_type != "code" {
+            continue;
+        }
+        for line in cell.source_lines() {
+            if let Some((kind, target)) = magic_target(dir, line) {
+                add_reference(&mut references, path, kind, Some(index), target);
+            }
+        }
+        for module in python::analyze_cell(cell).modules {
+            let target = module_path(dir, &module);
+            add_reference(
+                &mut references,
+                path,
+                ReferenceKind::Import(module),
+                Some(index),
+                target,
+            );
+        }
+    }
+    references
+}
+
+/// Adds a reference unless its target is missing or already referenced.
+fn add_reference(
+    references: &mut Vec<Reference>,
+    from: &Path,
+    kind: ReferenceKind,
+    cell_index: Option<usize>,
+    target: Option<PathBuf>,
+) {
+    let Some(target) = target else {
+        return;
+    };
+    if !references
+        .iter()
+        .any(|reference| reference.target == target)
+    {
+        references.push(Reference {
+            kind,
+            from: from.to_path_buf(),
+            cell_index,
+            target,
+        });
+    }
+}
+
+/// Parses a `%run` or `%load` line into its reference and target file.
+fn magic_target(dir: &Path, line: &str) -> Option<(ReferenceKind, Option<PathBuf>)> {
+    let line = line.trim();
+    let (is_run, rest) = if let Some(rest) = line.strip_prefix("%run") {
+        (true, rest)
+    } else {
+        (false, line.strip_prefix("%load")?)
+    };
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        // `%load_ext` and other magics that share the prefix.
+        return None;
+    }
+
+    let mut words = rest.split_whitespace();
+    while let Some(word) = words.next() {
+        match (is_run, word) {
+            (true, "-m") => {
+                let module = words.next()?;
+                let target = module_path(dir, module);
+                return Some((ReferenceKind::Run(format!("-m {}", module)), target));
+            }
+            (true, "-N" | "-b") | (false, "-r" | "-s" | "-n") => {
+                words.next();
+            }
+            (_, option) if option.starts_with('-') => {}
+            (_, target) => {
+                let target = target.trim_matches(['"', '\'']);
+                let path = (!target.contains("://"))
+                    .then(|| run_path(dir, target, is_run))
+                    .flatten();
+                let kind = if is_run {
+                    ReferenceKind::Run(target.to_string())
+                } else {
+                    ReferenceKind::Load(target.to_string())
+                };
+                return Some((kind, path));
+            }
+        }
+    }
+    None
+}
+
+/// `%run` also finds `prep` as `prep.py` or `prep.ipynb`.
+fn run_path(dir: &Path, target: &str, is_run: bool) -> Option<PathBuf> {
+    let path = join(dir, target);
+    if path.is_file() {
+        return Some(path);
+    }
+    if !is_run || path.extension().is_some() {
+        return None;
+    }
+    ["py", "ipynb"]
+        .into_iter()
+        .map(|extension| path.with_extension(extension))
+        .find(|path| path.is_file())
+}
+
+/// Joins a path as written in a notebook to its directory, dropping `.`
+/// components so that `./prep.ipynb` is shown as `prep.ipynb`.
+pub(crate) fn join(dir: &Path, target: &str) -> PathBuf {
+    dir.join(target)
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+/// Maps a module name as imported from `dir`, such as `utils` or `..lib.io`,
+/// to its `.py` file or package `__init__.py`.
+pub(crate) fn module_path(dir: &Path, module: &str) -> Option<PathBuf> {
+    let name = module.trim_start_matches('.');
+    if name.is_empty() {
+        return None;
+    }
+    let mut base = dir.to_path_buf();
+    for _ in 1..module.len() - name.len() {
+        if matches!(base.components().next_back(), Some(Component::Normal(_))) {
+            base.pop();
+        } else {
+            base.push("..");
+        }
+    }
+    let relative: PathBuf = name.split('.').collect();
+    let file = base.join(&relative).with_extension("py");
+    if file.is_file() {
+        return Some(file);
+    }
+    let package = base.join(relative).join("__init__.py");
+    package.is_file().then_some(package)
+}
+
+fn is_notebook(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "ipynb")
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Whether `path` is inside `dir` once symlinks and `..` are resolved.
+pub(crate) fn is_within(dir: &Path, path: &Path) -> bool {
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    match (dir.canonicalize(), path.canonicalize()) {
+        (Ok(dir), Ok(path)) => path.starts_with(dir),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_magic_target_parsing() {
+        let dir = Path::new("/nonexistent");
+        let kind = |line: &str| magic_target(dir, line).map(|(kind, _)| kind);
+        assert_eq!(
+            kind("%run -i ./prep.ipynb"),
+            Some(ReferenceKind::Run("./prep.ipynb".to_string()))
+        );
+        assert_eq!(
+            kind("%load -r 1-10 'helpers.py'"),
+            Some(ReferenceKind::Load("helpers.py".to_string()))
+        );
+        assert_eq!(
+            kind("%run -m tools.train"),
+            Some(ReferenceKind::Run("-m tools.train".to_string()))
+        );
+        assert_eq!(kind("%load_ext autoreload"), None);
+        assert_eq!(kind("x = 1"), None);
+    }
+
+    #[test]
+    fn test_module_path() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("project/notebooks");
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+        std::fs::write(dir.join("utils.py"), "").unwrap();
+        std::fs::write(dir.join("lib/__init__.py"), "").unwrap();
+        std::fs::write(root.path().join("project/shared.py"), "").unwrap();
+
+        assert_eq!(module_path(&dir, "utils"), Some(dir.join("utils.py")));
+        assert_eq!(module_path(&dir, "lib"), Some(dir.join("lib/__init__.py")));
+        assert_eq!(
+            module_path(&dir, "..shared"),
+            Some(root.path().join("project/shared.py"))
+        );
+        assert_eq!(module_path(&dir, "numpy"), None);
+        assert_eq!(module_path(&dir, "."), None);
+    }
+
+    #[test]
+    fn test_targets_outside_the_notebook_directory() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("project");
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::write(root.path().join("outside.py"), "SECRET = 1\n").unwrap();
+        std::fs::write(root.path().join("absolute.py"), "SECRET = 2\n").unwrap();
+        std::fs::write(dir.join("inside.py"), "X = 1\n").unwrap();
+        let source = format!(
+            "%load ../outside.py\n%load {}\n%load inside.py",
+            root.path().join("absolute.py").display()
+        );
+        let notebook = serde_json::json!({
+            "cells": [{"cell_type": "code", "execution_count": 1, "metadata": {},
+                       "outputs": [], "source": source}],
+            "metadata": {}, "nbformat": 4, "nbformat_minor": 4,
+        });
+        let notebook: Notebook = serde_json::from_value(notebook).unwrap();
+
+        let resolution = resolve(
+            &JupyterConverter::new(),
+            &dir.join("nb.ipynb"),
+            &notebook,
+            ReferenceLimits::default(),
+        );
+        let included: Vec<&Path> = resolution
+            .files
+            .iter()
+            .map(|file| file.reference.target.as_path())
+            .collect();
+        assert_eq!(included, [dir.join("inside.py")]);
+        let skipped: Vec<&str> = resolution
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.code == "reference-skipped")
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+        assert_eq!(skipped.len(), 2);
+        assert!(skipped[0].starts_with("`%load ../outside.py` in cell 1 of"));
+        assert!(skipped[0].ends_with("is outside the notebook's directory"));
+    }
+}
@@ -0,0 +1,254 @@
+//! Standalone Python script export.
+//!
+//! Code cells are ordered so that every cell runs after the cells it reads
+//! names from, keeping document order wherever the dependencies allow it.
+//! Cells whose bindings are all rebound later and never read are dropped,
+//! magics and shell escapes are commented out and top-level imports are
+//! hoisted to the top of the script.
+
+use crate::deps::DependencyGraph;
+use crate::python;
+use crate::Notebook;
+use std::collections::BTreeSet;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScriptExport {
+    /// The generated Python source.
+    pub script: String,
+    /// Indices of the exported code cells, in script order.
+    pub order: Vec<usize>,
+    /// Indices of the code cells left out as dead.
+    pub dropped: Vec<usize>,
+}
+
+/// Exports the code cells of a notebook as a runnable script.
+pub fn export_script(notebook: &Notebook) -> ScriptExport {
+    let graph = DependencyGraph::build(notebook);
+    let dropped = dead_cells(&graph);
+    let order = dependency_order(&graph, &dropped);
+
+    let mut imports: Vec<String> = Vec::new();
+    let mut bodies = Vec::new();
+    for &index in &order {
+        let lines: Vec<&str> = notebook.cells[index].source_lines().collect();
+        let source = lines.join("\n");
+        let mut body: Vec<String> = if python::is_foreign_cell(&source) {
+            lines.iter().map(|line| comment_out(line)).collect()
+        } else {
+            hoist_imports(&lines, &mut imports)
+        };
+        while body.last().is_some_and(|line| line.trim().is_empty()) {
+            body.pop();
+        }
+        let leading_blank = body
+            .iter()
+            .take_while(|line| line.trim().is_empty())
+            .count();
+        body.drain(..leading_blank);
+        if !body.is_empty() {
+            bodies.push((index, body));
+        }
+    }
+
+    let mut script = String::from("# Exported by jupyter2llm: code cells in dependency order.\n");
+    if !dropped.is_empty() {
+        let cells: Vec<String> = dropped
+            .iter()
+            .map(|index| (index + 1).to_string())
+            .collect();
+        script.push_str(&format!(
+            "# Dropped cells whose definitions are overwritten and never used: {}\n",
+            cells.join(", ")
+        ));
+    }
+    if !imports.is_empty() {
+        script.push('\n');
+        for import in &imports {
+            script.push_str(import);
+            script.push('\n');
+        }
+    }
+    for (index, body) in bodies {
+        script.push_str(&format!("\n\n# %% Cell {}\n", index + 1));
+        for line in body {
+            script.push_str(&line);
+            script.push('\n');
+        }
+    }
+
+    ScriptExport {
+        script,
+        order,
+        dropped: dropped.into_iter().collect(),
+    }
+}
+
+/// Finds cells that bind names, all of which a later cell rebinds, and that
+/// no cell reads from. Dropping a cell can leave its own dependencies dead,
+/// so this repeats until nothing changes.
+fn dead_cells(graph: &DependencyGraph) -> BTreeSet<usize> {
+    let mut dead = BTreeSet::new();
+    loop {
+        let newly_dead: Vec<usize> = graph
+            .cells
+            .iter()
+            .filter(|(index, _)| !dead.contains(*index))
+            .filter(|(&index, names)| {
+                let mut bindings = names.bindings().peekable();
+                let overwritten = bindings.peek().is_some()
+                    && bindings.all(|name| {
+                        graph
+                            .cells
+                            .range(index + 1..)
+                            .any(|(_, later)| later.binds(name))
+                    });
+                overwritten
+                    && graph
+                        .dependents(index)
+                        .all(|edge| dead.contains(&edge.cell))
+            })
+            .map(|(&index, _)| index)
+            .collect();
+
+        if newly_dead.is_empty() {
+            return dead;
+        }
+        dead.extend(newly_dead);
+    }
+}
+
+/// Topologically sorts the live code cells, always taking the earliest ready
+/// cell. Cycles are broken by taking the earliest remaining cell.
+fn dependency_order(graph: &DependencyGraph, dropped: &BTreeSet<usize>) -> Vec<usize> {
+    let mut remaining: BTreeSet<usize> = graph
+        .cells
+        .keys()
+        .filter(|index| !dropped.contains(index))
+        .copied()
+        .collect();
+    let mut order = Vec::new();
+
+    while !remaining.is_empty() {
+        let ready = remaining
+            .iter()
+            .copied()
+            .find(|&index| {
+                graph
+                    .dependencies(index)
+                    .all(|edge| !remaining.contains(&edge.source) || edge.source == index)
+            })
+            .or_else(|| remaining.first().copied())
+            .expect("remaining is not empty");
+        remaining.remove(&ready);
+        order.push(ready);
+    }
+
+    order
+}
+
+/// Moves top-level import statements into `imports`, skipping duplicates,
+/// and returns the remaining lines with IPython syntax commented out.
+fn hoist_imports(lines: &[&str], imports: &mut Vec<String>) -> Vec<String> {
+    let mut body = Vec::new();
+    let mut position = 0;
+
+    while position < lines.len() {
+        let line = lines[position];
+        let is_import = line.starts_with("import ") || line.starts_with("from ");
+        if is_import {
+            // Parenthesized and backslash-continued imports span lines.
+            let mut statement = line.to_string();
+            let mut depth = bracket_depth(line);
+            while (depth > 0 || statement.ends_with('\\')) && position + 1 < lines.len() {
+                position += 1;
+                statement.push('\n');
+                statement.push_str(lines[position]);
+                depth += bracket_depth(lines[position]);
+            }
+            if !imports.contains(&statement) {
+                imports.push(statement);
+            }
+        } else if python::is_ipython_line(line) {
+            body.push(comment_out(line));
+        } else {
+            body.push(line.to_string());
+        }
+        position += 1;
+    }
+
+    body
+}
+
+fn bracket_depth(line: &str) -> i32 {
+    let code = line.split('#').next().unwrap_or("");
+    code.matches('(').count() as i32 - code.matches(')').count() as i32
+}
+
+/// Comments out a line, keeping nested blocks valid with `pass`.
+fn comment_out(line: &str) -> String {
+    let trimmed = line.trim_start();
+    if trimmed.is_empty() {
+        return String::new();
+    }
+    let indent = &line[..line.len() - trimmed.len()];
+    if indent.is_empty() {
+        format!("# {}", trimmed)
+    } else {
+        format!("{}pass  # {}", indent, trimmed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_export() {
+        let notebook: Notebook = r##"{
+ "cells": [
+  {"cell_type": "markdown", "metadata": {}, "source": ["# Pipeline"]},
+  {"cell_type": "code", "execution_count": 1, "metadata": {}, "outputs": [], "source": ["import os\n", "%matplotlib inline\n", "from pathlib import (\n", "    Path,\n", ")"]},
+  {"cell_type": "code", "execution_count": 6, "metadata": {}, "outputs": [], "source": ["summary = summarize(data)"]},
+  {"cell_type": "code", "execution_count": 2, "metadata": {}, "outputs": [], "source": ["scale = 1"]},
+  {"cell_type": "code", "execution_count": 3, "metadata": {}, "outputs": [], "source": ["data = load(Path('data.csv'))\n", "!ls data"]},
+  {"cell_type": "code", "execution_count": 4, "metadata": {}, "outputs": [], "source": ["def summarize(frame):\n", "    import json\n", "    return json.dumps(frame)"]},
+  {"cell_type": "code", "execution_count": 7, "metadata": {}, "outputs": [], "source": ["scale = 2\n", "import os"]},
+  {"cell_type": "code", "execution_count": 8, "metadata": {}, "outputs": [], "source": ["print(scale, summary)"]},
+  {"cell_type": "code", "execution_count": 9, "metadata": {}, "outputs": [], "source": ["%%bash\n", "echo done"]}
+ ],
+ "metadata": {},
+ "nbformat": 4,
+ "nbformat_minor": 4
+}"##
+        .parse()
+        .unwrap();
+        let export = export_script(&notebook);
+
+        assert_eq!(export.order, vec![1, 4, 5, 2, 6, 7, 8]);
+        assert_eq!(export.dropped, vec![3]);
+        assert_eq!(
+            export.script,
+            "# Exported by jupyter2llm: code cells in dependency order.\n\
+             # Dropped cells whose definitions are overwritten and never used: 4\n\
+             \n\
+             import os\n\
+             from pathlib import (\n    Path,\n)\n\
+             \n\n# %% Cell 2\n\
+             # %matplotlib inline\n\
+             \n\n# %% Cell 5\n\
+             data = load(Path('data.csv'))\n\
+             # !ls data\n\
+             \n\n# %% Cell 6\n\
+             def summarize(frame):\n    import json\n    return json.dumps(frame)\n\
+             \n\n# %% Cell 3\n\
+             summary = summarize(data)\n\
+             \n\n# %% Cell 7\n\
+             scale = 2\n\
+             \n\n# %% Cell 8\n\
+             print(scale, summary)\n\
+             \n\n# %% Cell 9\n\
+             # %%bash\n\
+             # echo done\n"
+        );
+    }
+}
@@ -0,0 +1,293 @@
+//! Heading-tree analysis over markdown cells.
+//!
+//! Markdown headings split a notebook into nested sections. A section starts at
+//! the cell holding its heading and runs until the next heading of the same or
+//! a higher level.
+
+use crate::{Cell, Notebook};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heading {
+    pub level: usize,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub title: String,
+    pub level: usize,
+    /// Titles of all enclosing headings, outermost first, ending with `title`.
+    pub path: Vec<String>,
+    /// Index of the cell holding the heading.
+    pub start: usize,
+    /// Index one past the last cell of the section.
+    pub end: usize,
+}
+
+impl Section {
+    pub fn path_string(&self) -> String {
+        self.path.join("/")
+    }
+
+    pub fn contains(&self, cell_index: usize) -> bool {
+        (self.start..self.end).contains(&cell_index)
+    }
+}
+
+/// Extracts ATX (`## Title`) and setext (`Title\n-----`) headings from a
+/// markdown cell, ignoring anything inside fenced code blocks.
+pub fn markdown_headings(cell: &Cell) -> Vec<Heading> {
+    let mut headings = Vec::new();
+    if cell.cell_type != "markdown" {
+        return headings;
+    }
+
+    let mut in_fence = false;
+    let mut previous: Option<&str> = None;
+
+    for line in cell.source_lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            previous = None;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+
+        if let Some(heading) = parse_atx_heading(line) {
+            headings.push(heading);
+            previous = None;
+            continue;
+        }
+
+        if let Some(level) = setext_level(line) {
+            if let Some(text) = previous.filter(|text| !text.trim().is_empty()) {
+                headings.push(Heading {
+                    level,
+                    title: text.trim().to_string(),
+                });
+                previous = None;
+                continue;
+            }
+        }
+
+        previous = Some(line);
+    }
+
+    headings
+}
+
+fn parse_atx_heading(line: &str) -> Option<Heading> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    if indent > 3 {
+        return None;
+    }
+    let rest = &line[indent..];
+    let level = rest.chars().take_while(|&c| c == '#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let rest = &rest[level..];
+    if !rest.is_empty() && !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+
+    // A closing run of `#` is only stripped after a space, so `## C#`
+    // keeps its title.
+    let title = rest.trim();
+    let unclosed = title.trim_end_matches('#');
+    let title = if unclosed.is_empty() || unclosed.ends_with([' ', '\t']) {
+        unclosed.trim_end()
+    } else {
+        title
+    };
+    if title.is_empty() {
+        return None;
+    }
+
+    Some(Heading {
+        level,
+        title: title.to_string(),
+    })
+}
+
+fn setext_level(line: &str) -> Option<usize> {
+    let trimmed = line.trim();
+    if trimmed.is_empty() || line.len() - line.trim_start().len() > 3 {
+        return None;
+    }
+    if trimmed.chars().all(|c| c == '=') {
+        Some(1)
+    } else if trimmed.chars().all(|c| c == '-') {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+/// Builds the flattened heading tree of a notebook, in document order.
+pub fn sections(notebook: &Notebook) -> Vec<Section> {
+    let headings: Vec<(usize, Heading)> = notebook
+        .cells
+        .iter()
+        .enumerate()
+        .flat_map(|(index, cell)| {
+            markdown_headings(cell)
+                .into_iter()
+                .map(move |heading| (index, heading))
+        })
+        .collect();
+
+    let mut sections = Vec::with_capacity(headings.len());
+    let mut stack: Vec<(usize, String)> = Vec::new();
+
+    for (position, (start, heading)) in headings.iter().enumerate() {
+        while stack
+            .last()
+            .is_some_and(|(level, _)| *level >= heading.level)
+        {
+            stack.pop();
+        }
+        stack.push((heading.level, heading.title.clone()));
+
+        let end = headings[position + 1..]
+            .iter()
+            .find(|(_, next)| next.level <= heading.level)
+            .map_or(notebook.cells.len(), |(index, _)| *index)
+            .max(start + 1);
+
+        sections.push(Section {
+            title: heading.title.clone(),
+            level: heading.level,
+            path: stack.iter().map(|(_, title)| title.clone()).collect(),
+            start: *start,
+            end,
+        });
+    }
+
+    sections
+}
+
+/// Looks up a section by its slash-separated path, e.g. `"Training/Evaluation"`.
+///
+/// An exact match on the full path wins; otherwise the first section whose
+/// path ends with the given components is returned. Comparison ignores case.
+pub fn find_section<'a>(sections: &'a [Section], path: &str) -> Option<&'a Section> {
+    let wanted: Vec<String> = path
+        .split('/')
+        .map(|part| part.trim().to_lowercase())
+        .filter(|part| !part.is_empty())
+        .collect();
+    if wanted.is_empty() {
+        return None;
+    }
+
+    let matches_tail = |section: &Section| {
+        section.path.len() >= wanted.len()
+            && section.path[section.path.len() - wanted.len()..]
+                .iter()
+                .zip(&wanted)
+                .all(|(title, part)| title.to_lowercase() == *part)
+    };
+
+    sections
+        .iter()
+        .find(|section| section.path.len() == wanted.len() && matches_tail(section))
+        .or_else(|| sections.iter().find(|section| matches_tail(section)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markdown(lines: &[&str]) -> Cell {
+        Cell {
+            cell_type: "markdown".to_string(),
+            source: lines.iter().map(|line| line.to_string()).collect(),
+            metadata: Default::default(),
+            outputs: None,
+            execution_count: None,
+        }
+    }
+
+    #[test]
+    fn test_atx_and_setext_headings() {
+        let cell = markdown(&[
+            "# Title #\n",
+            "text\n",
+            "Sub\n",
+            "---\n",
+            "```\n",
+            "# not\n",
+            "```",
+        ]);
+        let headings = markdown_headings(&cell);
+        assert_eq!(
+            headings,
+            vec![
+                Heading {
+                    level: 1,
+                    title: "Title".to_string()
+                },
+                Heading {
+                    level: 2,
+                    title: "Sub".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hashtag_is_not_heading() {
+        assert!(parse_atx_heading("#hashtag").is_none());
+        assert!(parse_atx_heading("####### too deep").is_none());
+    }
+
+    #[test]
+    fn test_closing_hashes_need_a_space() {
+        let title = |line: &str| parse_atx_heading(line).map(|heading| heading.title);
+        assert_eq!(title("## C#"), Some("C#".to_string()));
+        assert_eq!(title("## C# ##"), Some("C#".to_string()));
+        assert_eq!(title("# Title #"), Some("Title".to_string()));
+        assert_eq!(title("## ##"), None);
+    }
+
+    #[test]
+    fn test_section_paths() {
+        let code = |source: &str| Cell {
+            cell_type: "code".to_string(),
+            ..markdown(&[source])
+        };
+        let notebook = Notebook {
+            cells: vec![
+                markdown(&["# Tutorial\n", "Intro text"]),
+                code("import torch"),
+                markdown(&["## Training"]),
+                code("model.fit()"),
+                markdown(&["### Evaluation"]),
+                code("model.evaluate()"),
+                markdown(&["Deployment\n", "----------"]),
+                code("model.save()"),
+            ],
+            metadata: Default::default(),
+            nbformat: 4,
+            nbformat_minor: 4,
+        };
+        let paths: Vec<String> = sections(&notebook)
+            .iter()
+            .map(|section| section.path_string())
+            .collect();
+
+        assert_eq!(
+            paths,
+            vec![
+                "Tutorial",
+                "Tutorial/Training",
+                "Tutorial/Training/Evaluation",
+                "Tutorial/Deployment"
+            ]
+        );
+    }
+}
@@ -0,0 +1,780 @@
+//! Previews of the data files a notebook reads.
+//!
+//! Code like `pd.read_csv("data/sales.csv")` leaves a model guessing at the
+//! shape of the data. The sniffer looks for string literals in code cells
+//! that name an existing data file relative to the notebook and previews
+//! each one by its format: delimited text with its columns, their inferred
+//! types and a sample of rows, JSON and JSON Lines with a sketch of their
+//! structure, and Parquet and Excel files with their size only. Only the
+//! head of each file is read, and previews are redacted, scrubbed and
+//! anonymized like the notebook. Files outside the notebook's directory are
+//! not previewed.
+
+use crate::diagnostic::Diagnostic;
+use crate::{bundle, python, recovery, resolve, JupyterConverter, JupyterError, Notebook};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::fmt;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Rows sampled from a delimited file by default.
+pub const DEFAULT_SAMPLE_ROWS: usize = 5;
+/// How much of each data file is read by default.
+pub const DEFAULT_MAX_BYTES: u64 = 64 * 1024;
+
+/// Columns shown for wide delimited files.
+const MAX_COLUMNS: usize = 30;
+/// Characters shown of a sampled value.
+const MAX_VALUE_CHARS: usize = 40;
+/// Levels of nesting expanded in a JSON sketch.
+const MAX_SKETCH_DEPTH: usize = 4;
+/// Keys shown per object in a JSON sketch.
+const MAX_KEYS: usize = 25;
+/// Array elements looked at per level of a JSON sketch.
+const MAX_ELEMENTS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataLimits {
+    pub sample_rows: usize,
+    /// Bytes read from the start of each file.
+    pub max_bytes: u64,
+}
+
+impl Default for DataLimits {
+    fn default() -> Self {
+        Self {
+            sample_rows: DEFAULT_SAMPLE_ROWS,
+            max_bytes: DEFAULT_MAX_BYTES,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataFormat {
+    Csv,
+    Tsv,
+    Json,
+    JsonLines,
+    Parquet,
+    Excel,
+}
+
+impl DataFormat {
+    /// The format of a data file by its extension.
+    pub fn of(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "csv" => Some(DataFormat::Csv),
+            "tsv" | "tab" => Some(DataFormat::Tsv),
+            "json" => Some(DataFormat::Json),
+            "jsonl" | "ndjson" => Some(DataFormat::JsonLines),
+            "parquet" | "pq" => Some(DataFormat::Parquet),
+            "xlsx" | "xlsm" | "xls" => Some(DataFormat::Excel),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DataFormat::Csv => "CSV",
+            DataFormat::Tsv => "TSV",
+            DataFormat::Json => "JSON",
+            DataFormat::JsonLines => "JSON Lines",
+            DataFormat::Parquet => "Parquet",
+            DataFormat::Excel => "Excel",
+        }
+    }
+}
+
+impl fmt::Display for DataFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataFile {
+    pub path: PathBuf,
+    /// Zero-based index of the first cell that names the file.
+    pub cell_index: usize,
+    pub format: DataFormat,
+    pub size: u64,
+    pub preview: String,
+}
+
+/// The data files of a notebook in the order its cells name them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DataPreviews {
+    pub files: Vec<DataFile>,
+    /// Files that could not be previewed, redactions and scrubbed values.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl fmt::Display for DataPreviews {
+    /// The appendix with one delimited section per file, or nothing.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.files.is_empty() {
+            return Ok(());
+        }
+        write!(f, "# Data Files\n\n")?;
+        for file in &self.files {
+            let text = format!(
+                "*Read in cell {}: {}, {}*\n\n{}",
+                file.cell_index + 1,
+                file.format,
+                size_label(file.size),
+                file.preview
+            );
+            writeln!(f, "{}", bundle::file_section(&file.path, &text))?;
+        }
+        Ok(())
+    }
+}
+
+/// Previews the data files read by `notebook`, loaded from `path`.
+pub fn sniff(
+    converter: &JupyterConverter,
+    path: &Path,
+    notebook: &Notebook,
+    limits: DataLimits,
+) -> DataPreviews {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut previews = DataPreviews::default();
+
+    for (cell_index, file) in find_data_files(path, notebook) {
+        let Some(format) = DataFormat::of(&file) else {
+            continue;
+        };
+        if !resolve::is_within(dir, &file) {
+            previews.diagnostics.push(Diagnostic::warning(
+                Some(cell_index),
+                "data-file-skipped",
+                format!(
+                    "{} not previewed, it is outside the notebook's directory",
+                    file.display()
+                ),
+            ));
+            continue;
+        }
+        // Values are filtered before they are parsed, since sampled values
+        // are shortened and a cut secret no longer matches its rule.
+        let mut redactions = Vec::new();
+        let mut findings = Vec::new();
+        let filter = |text: &mut String| {
+            if let Some(redactor) = &converter.redactor {
+                redactions = redactor.redact_text(text);
+            }
+            if let Some(scrubber) = &converter.pii_scrubber {
+                findings = scrubber.scrub_text(text);
+            }
+            if let Some(anonymizer) = &converter.anonymizer {
+                *text = anonymizer.anonymize_text(text);
+            }
+        };
+        let previewed = std::fs::metadata(&file)
+            .map_err(JupyterError::from)
+            .and_then(|metadata| {
+                let preview = preview_filtered(&file, format, limits, filter)?;
+                Ok((metadata.len(), preview))
+            });
+        let (size, preview) = match previewed {
+            Ok(previewed) => previewed,
+            Err(err) => {
+                previews.diagnostics.push(Diagnostic::warning(
+                    Some(cell_index),
+                    "data-file-skipped",
+                    format!("{} could not be previewed: {}", file.display(), err),
+                ));
+                continue;
+            }
+        };
+        for (rule, count) in redactions {
+            previews.diagnostics.push(Diagnostic::note(
+                Some(cell_index),
+                "redacted",
+                format!(
+                    "replaced {} {} match(es) in the preview of {}",
+                    count,
+                    rule,
+                    file.display()
+                ),
+            ));
+        }
+        for (kind, count) in findings {
+            previews.diagnostics.push(Diagnostic::note(
+                Some(cell_index),
+                "scrubbed",
+                format!(
+                    "replaced {} {} value(s) in the preview of {}",
+                    count,
+                    kind,
+                    file.display()
+                ),
+            ));
+        }
+        previews.files.push(DataFile {
+            path: file,
+            cell_index,
+            format,
+            size,
+            preview,
+        });
+    }
+    previews
+}
+
+/// Finds the string literals of code cells that name an existing data file
+/// relative to the notebook's directory, with the first cell naming each.
+pub fn find_data_files(path: &Path, notebook: &Notebook) -> Vec<(usize, PathBuf)> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut seen = HashSet::new();
+    let mut files = Vec::new();
+
+    for (index, cell) in notebook.cells.iter().enumerate() {
+        if cell.cell_type != "code" {
+            continue;
+        }
+        let source: Vec<&str> = cell.source_lines().collect();
+        for literal in python::string_literals(&source.join("\n")) {
+            if literal.contains("://") || DataFormat::of(Path::new(&literal)).is_none() {
+                continue;
+            }
+            let file = resolve::join(dir, &literal);
+            if !file.is_file() {
+                continue;
+            }
+            let key = file.canonicalize().unwrap_or_else(|_| file.clone());
+            if seen.insert(key) {
+                files.push((index, file));
+            }
+        }
+    }
+    files
+}
+
+/// Previews one data file from its first `limits.max_bytes` bytes.
+pub fn preview(
+    path: &Path,
+    format: DataFormat,
+    limits: DataLimits,
+) -> Result<String, JupyterError> {
+    preview_filtered(path, format, limits, |_| {})
+}
+
+/// Like `preview`, passing the text read to `filter` before parsing it.
+fn preview_filtered(
+    path: &Path,
+    format: DataFormat,
+    limits: DataLimits,
+    filter: impl FnOnce(&mut String),
+) -> Result<String, JupyterError> {
+    if matches!(format, DataFormat::Parquet | DataFormat::Excel) {
+        return Ok("Binary file, contents not previewed.".to_string());
+    }
+    let mut head = Head::read(path, limits.max_bytes)?;
+    filter(&mut head.text);
+
+    let preview = match format {
+        DataFormat::Csv => preview_delimited(&head, None, limits.sample_rows),
+        DataFormat::Tsv => preview_delimited(&head, Some('\t'), limits.sample_rows),
+        DataFormat::Json if head.truncated => match recovery::salvage_truncated(&head.text) {
+            Some((value, kept)) => format!(
+                "Partial sketch of the first {} bytes, the file is larger than the {}-byte preview limit:\n\n{}",
+                kept,
+                limits.max_bytes,
+                fenced_sketch(&[&value], None)
+            ),
+            None => format!(
+                "Not sketched: the file is larger than the {}-byte preview limit.",
+                limits.max_bytes
+            ),
+        },
+        DataFormat::Json => {
+            let value: Value = serde_json::from_str(&head.text)?;
+            fenced_sketch(&[&value], None)
+        }
+        DataFormat::JsonLines => preview_json_lines(&head)?,
+        DataFormat::Parquet | DataFormat::Excel => unreachable!("binary formats return early"),
+    };
+    Ok(preview.trim_end().to_string())
+}
+
+/// The complete lines read from the start of a file.
+struct Head {
+    text: String,
+    /// Bytes of the complete lines, before filtering.
+    read: usize,
+    truncated: bool,
+    size: u64,
+}
+
+impl Head {
+    fn read(path: &Path, max_bytes: u64) -> Result<Self, JupyterError> {
+        let size = std::fs::metadata(path)?.len();
+        let mut bytes = Vec::new();
+        std::fs::File::open(path)?
+            .take(max_bytes)
+            .read_to_end(&mut bytes)?;
+        let truncated = (bytes.len() as u64) < size;
+        if truncated {
+            // Drop the partial record at the cut.
+            bytes.truncate(
+                bytes
+                    .iter()
+                    .rposition(|&byte| byte == b'\n')
+                    .map_or(0, |end| end + 1),
+            );
+        }
+        Ok(Self {
+            text: String::from_utf8_lossy(&bytes).into_owned(),
+            read: bytes.len(),
+            truncated,
+            size,
+        })
+    }
+
+    /// The number of records in the file, estimated from those read when
+    /// the file was cut.
+    fn count(&self, records: usize, label: &str) -> String {
+        if !self.truncated {
+            return format!("{}: {}", label, records);
+        }
+        let read = self.read.max(1) as u64;
+        format!(
+            "{}: ~{} (estimated from the first {} records)",
+            label,
+            records as u64 * self.size / read,
+            records
+        )
+    }
+}
+
+fn preview_delimited(head: &Head, delimiter: Option<char>, sample_rows: usize) -> String {
+    let delimiter = delimiter.unwrap_or_else(|| sniff_delimiter(&head.text));
+    let mut records = parse_delimited(&head.text, delimiter);
+    if records.is_empty() {
+        return "Empty file.".to_string();
+    }
+    let header = records.remove(0);
+    let shown = header.len().min(MAX_COLUMNS);
+
+    let columns: Vec<String> = header
+        .iter()
+        .enumerate()
+        .take(shown)
+        .map(|(column, name)| {
+            let kind = records
+                .iter()
+                .filter_map(|record| record.get(column))
+                .fold(ValueType::Empty, |kind, value| {
+                    kind.merge(ValueType::of(value))
+                });
+            format!("{} ({})", name, kind.as_str())
+        })
+        .collect();
+    let mut preview = format!("Columns ({}): {}", header.len(), columns.join(", "));
+    if header.len() > shown {
+        preview.push_str(&format!(", and {} more", header.len() - shown));
+    }
+    preview.push('\n');
+    preview.push_str(&head.count(records.len(), "Rows"));
+    preview.push('\n');
+
+    let sample = sample(&records, sample_rows);
+    if sample.is_empty() {
+        return preview;
+    }
+    let row = |values: &[String]| {
+        let cells: Vec<String> = (0..shown)
+            .map(|column| table_value(values.get(column).map_or("", String::as_str)))
+            .collect();
+        format!("| {} |\n", cells.join(" | "))
+    };
+    preview.push('\n');
+    preview.push_str(&row(&header));
+    preview.push_str(&format!("|{}\n", "---|".repeat(shown)));
+    for record in sample {
+        preview.push_str(&row(record));
+    }
+    preview
+}
+
+/// Picks evenly spaced rows, always including the first.
+fn sample(records: &[Vec<String>], rows: usize) -> Vec<&Vec<String>> {
+    if records.len() <= rows {
+        return records.iter().collect();
+    }
+    (0..rows)
+        .map(|row| &records[row * records.len() / rows])
+        .collect()
+}
+
+/// The most frequent of the common delimiters in the header line.
+fn sniff_delimiter(text: &str) -> char {
+    let header = text.lines().next().unwrap_or("");
+    let mut best = (',', 0);
+    for delimiter in [',', ';', '\t', '|'] {
+        let count = header.matches(delimiter).count();
+        if count > best.1 {
+            best = (delimiter, count);
+        }
+    }
+    best.0
+}
+
+/// Splits delimited text into records, with quoted fields that may contain
+/// delimiters, doubled quotes and line breaks. Blank lines are skipped.
+fn parse_delimited(text: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                '"' => quoted = false,
+                c => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => quoted = true,
+            '\r' => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            c if c == delimiter => record.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records.retain(|record| !(record.len() == 1 && record[0].is_empty()));
+    records
+}
+
+/// A sampled value as a table cell.
+fn table_value(value: &str) -> String {
+    let value = value.replace('\n', " ").replace('|', "\\|");
+    if value.chars().count() <= MAX_VALUE_CHARS {
+        return value;
+    }
+    let mut shortened: String = value.chars().take(MAX_VALUE_CHARS - 1).collect();
+    shortened.push('…');
+    shortened
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ValueType {
+    Empty,
+    Bool,
+    Int,
+    Float,
+    Date,
+    Text,
+}
+
+impl ValueType {
+    fn of(value: &str) -> Self {
+        let value = value.trim();
+        if value.is_empty() {
+            ValueType::Empty
+        } else if value.eq_ignore_ascii_case("true") || value.eq_ignore_ascii_case("false") {
+            ValueType::Bool
+        } else if value.parse::<i64>().is_ok() {
+            ValueType::Int
+        } else if value.parse::<f64>().is_ok() {
+            ValueType::Float
+        } else if is_date(value) {
+            ValueType::Date
+        } else {
+            ValueType::Text
+        }
+    }
+
+    /// The type of a column holding values of both types.
+    fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (ValueType::Empty, kind) | (kind, ValueType::Empty) => kind,
+            (a, b) if a == b => a,
+            (ValueType::Int, ValueType::Float) | (ValueType::Float, ValueType::Int) => {
+                ValueType::Float
+            }
+            _ => ValueType::Text,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            ValueType::Empty => "empty",
+            ValueType::Bool => "bool",
+            ValueType::Int => "int",
+            ValueType::Float => "float",
+            ValueType::Date => "date",
+            ValueType::Text => "text",
+        }
+    }
+}
+
+/// An ISO date such as `2024-01-31`, optionally followed by a time.
+fn is_date(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    bytes.len() >= 10
+        && bytes[..10]
+            .iter()
+            .enumerate()
+            .all(|(index, &byte)| match index {
+                4 | 7 => byte == b'-',
+                _ => byte.is_ascii_digit(),
+            })
+        && matches!(bytes.get(10), None | Some(b' ' | b'T'))
+}
+
+fn preview_json_lines(head: &Head) -> Result<String, JupyterError> {
+    let mut records = Vec::new();
+    let mut invalid = 0;
+    for line in head.text.lines().filter(|line| !line.trim().is_empty()) {
+        match serde_json::from_str::<Value>(line) {
+            Ok(record) => records.push(record),
+            Err(_) => invalid += 1,
+        }
+    }
+    if records.is_empty() && invalid > 0 {
+        // Report why the first line is not JSON.
+        let first = head.text.lines().find(|line| !line.trim().is_empty());
+        serde_json::from_str::<Value>(first.unwrap_or_default())?;
+    }
+
+    let mut preview = head.count(records.len(), "Records");
+    if invalid > 0 {
+        preview.push_str(&format!(", {} line(s) not valid JSON", invalid));
+    }
+    preview.push('\n');
+    if !records.is_empty() {
+        let records: Vec<&Value> = records.iter().take(MAX_ELEMENTS).collect();
+        preview.push('\n');
+        preview.push_str(&fenced_sketch(&records, Some("record")));
+    }
+    Ok(preview)
+}
+
+/// The structure of JSON values, one key per line, in a text fence.
+fn fenced_sketch(values: &[&Value], label: Option<&str>) -> String {
+    let mut lines = Vec::new();
+    sketch(values, label, 0, &mut lines);
+    format!("```text\n{}\n```", lines.join("\n"))
+}
+
+/// Sketches the values found at one place in a document, such as all the
+/// records of a file or one key of all of them, with the union of the keys
+/// of their objects below.
+fn sketch(values: &[&Value], label: Option<&str>, depth: usize, lines: &mut Vec<String>) {
+    let indent = "  ".repeat(depth);
+    let (summary, objects) = shape(values);
+    let mut line = match label {
+        Some(label) => format!("{}{}: {}", indent, label, summary),
+        None => format!("{}{}", indent, summary),
+    };
+    if objects.is_empty() {
+        lines.push(line);
+        return;
+    }
+    if depth >= MAX_SKETCH_DEPTH {
+        line.push_str(" {...}");
+        lines.push(line);
+        return;
+    }
+    lines.push(line);
+
+    let mut keys: Vec<(&str, Vec<&Value>)> = Vec::new();
+    for object in objects {
+        for (key, value) in object {
+            match keys.iter_mut().find(|(name, _)| name == key) {
+                Some((_, values)) => values.push(value),
+                None => keys.push((key, vec![value])),
+            }
+        }
+    }
+    let more = keys.len().saturating_sub(MAX_KEYS);
+    for (key, values) in keys.into_iter().take(MAX_KEYS) {
+        sketch(&values, Some(key), depth + 1, lines);
+    }
+    if more > 0 {
+        lines.push(format!("{}  ... and {} more keys", indent, more));
+    }
+}
+
+/// The types of the values, such as `string | null` or `array (3 items) of
+/// object`, and the objects among them or in their arrays.
+fn shape<'v>(values: &[&'v Value]) -> (String, Vec<&'v Map<String, Value>>) {
+    let mut kinds: Vec<String> = Vec::new();
+    let mut objects = Vec::new();
+    let mut elements = Vec::new();
+    let mut arrays = 0;
+
+    for value in values {
+        let kind = match value {
+            Value::Null => "null",
+            Value::Bool(_) => "bool",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Array(items) => {
+                arrays += 1;
+                let room = MAX_ELEMENTS.saturating_sub(elements.len());
+                elements.extend(items.iter().take(room));
+                continue;
+            }
+            Value::Object(object) => {
+                objects.push(object);
+                "object"
+            }
+        };
+        if !kinds.iter().any(|existing| existing == kind) {
+            kinds.push(kind.to_string());
+        }
+    }
+
+    if arrays > 0 {
+        let length = match values {
+            [Value::Array(items)] if items.len() == 1 => " (1 item)".to_string(),
+            [Value::Array(items)] => format!(" ({} items)", items.len()),
+            _ => String::new(),
+        };
+        if elements.is_empty() {
+            kinds.push(format!("array{}", length));
+        } else {
+            let (inner, inner_objects) = shape(&elements);
+            kinds.push(format!("array{} of {}", length, inner));
+            objects.extend(inner_objects);
+        }
+    }
+    (kinds.join(" | "), objects)
+}
+
+fn size_label(bytes: u64) -> String {
+    const KIB: f64 = 1024.0;
+    match bytes as f64 {
+        size if size < KIB => format!("{} bytes", bytes),
+        size if size < KIB * KIB => format!("{:.1} KiB", size / KIB),
+        size if size < KIB * KIB * KIB => format!("{:.1} MiB", size / (KIB * KIB)),
+        size => format!("{:.1} GiB", size / (KIB * KIB * KIB)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delimited_columns_and_types() {
+        let records = parse_delimited(
+            "id;name;score;joined\n1;\"Smith; J\";0.5;2024-01-31\n2;Lee;;2024-02-01 10:00\n\n3;\"A \"\"B\"\"\";7;n/a\n",
+            sniff_delimiter("id;name;score;joined\n"),
+        );
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[1][1], "Smith; J");
+        assert_eq!(records[3][1], "A \"B\"");
+
+        let column = |index: usize| {
+            records[1..]
+                .iter()
+                .fold(ValueType::Empty, |kind, record| {
+                    kind.merge(ValueType::of(&record[index]))
+                })
+                .as_str()
+        };
+        assert_eq!(
+            [column(0), column(1), column(2), column(3)],
+            ["int", "text", "float", "text"]
+        );
+        assert!(is_date("2024-02-01 10:00"));
+        assert!(!is_date("2024-2-1"));
+    }
+
+    #[test]
+    fn test_json_sketch() {
+        let value: Value = serde_json::from_str(
+            r#"{"model": "resnet", "layers": [{"units": 64}, {"units": 10, "activation": "softmax"}], "notes": null}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            fenced_sketch(&[&value], None),
+            "```text\nobject\n  layers: array (2 items) of object\n    units: number\n    activation: string\n  model: string\n  notes: null\n```"
+        );
+        let single = serde_json::json!({"tags": ["a"]});
+        assert_eq!(
+            fenced_sketch(&[&single], None),
+            "```text\nobject\n  tags: array (1 item) of string\n```"
+        );
+    }
+
+    #[test]
+    fn test_truncated_json_sketch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("runs.json");
+        let runs: Vec<Value> = (0..200)
+            .map(|run| serde_json::json!({"run": run, "loss": 0.5}))
+            .collect();
+        let content = serde_json::to_string_pretty(&serde_json::json!({"runs": runs})).unwrap();
+        std::fs::write(&path, content).unwrap();
+        let limits = DataLimits {
+            max_bytes: 1024,
+            ..DataLimits::default()
+        };
+
+        let preview = preview_filtered(&path, DataFormat::Json, limits, |_| {}).unwrap();
+        assert!(preview.starts_with("Partial sketch of the first "));
+        assert!(preview.contains("larger than the 1024-byte preview limit"));
+        assert!(preview.ends_with(
+            "```text\nobject\n  runs: array (21 items) of object\n    loss: number\n    run: number\n```"
+        ));
+    }
+
+    #[test]
+    fn test_files_outside_the_notebook_directory() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("project");
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::write(root.path().join("secrets.csv"), "user,token\nbob,x\n").unwrap();
+        std::fs::write(root.path().join("other.csv"), "user,token\nada,y\n").unwrap();
+        std::fs::write(dir.join("sales.csv"), "day,amount\n1,2\n").unwrap();
+        let source = format!(
+            "a = pd.read_csv('../secrets.csv')\nb = pd.read_csv('{}')\nc = pd.read_csv('sales.csv')",
+            root.path().join("other.csv").display()
+        );
+        let notebook = serde_json::json!({
+            "cells": [{"cell_type": "code", "execution_count": 1, "metadata": {},
+                       "outputs": [], "source": source}],
+            "metadata": {}, "nbformat": 4, "nbformat_minor": 4,
+        });
+        let notebook: Notebook = serde_json::from_value(notebook).unwrap();
+
+        let previews = sniff(
+            &JupyterConverter::new(),
+            &dir.join("nb.ipynb"),
+            &notebook,
+            DataLimits::default(),
+        );
+        let files: Vec<&Path> = previews
+            .files
+            .iter()
+            .map(|file| file.path.as_path())
+            .collect();
+        assert_eq!(files, [dir.join("sales.csv")]);
+        assert_eq!(previews.diagnostics.len(), 2);
+        assert_eq!(previews.diagnostics[0].code, "data-file-skipped");
+        assert!(previews.diagnostics[0]
+            .message
+            .ends_with("not previewed, it is outside the notebook's directory"));
+    }
+}
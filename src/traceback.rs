@@ -0,0 +1,221 @@
+//! Traceback analysis for `error` outputs.
+//!
+//! IPython tracebacks list one entry per stack frame, and a single pandas or
+//! PyTorch exception can produce dozens of frames inside site-packages. The
+//! compact form keeps the frames from notebook cells and the final library
+//! frame, collapses the other library frames into a count and always keeps
+//! the exception type and message.
+
+use crate::Output;
+use regex::Regex;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TracebackMode {
+    /// Render the traceback exactly as stored, including ANSI colors.
+    #[default]
+    Full,
+    /// Keep user frames and the final library frame, without ANSI colors.
+    Compact,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    /// Separator and `Traceback (most recent call last)` lines.
+    Header,
+    /// A frame inside a notebook cell.
+    UserFrame,
+    /// A frame inside a module or library file.
+    LibraryFrame,
+    /// The exception line, or text between chained tracebacks.
+    Message,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TracebackEntry {
+    pub kind: EntryKind,
+    /// Entry text with ANSI escapes removed.
+    pub text: String,
+}
+
+/// Removes ANSI color escapes as emitted by IPython.
+pub fn strip_ansi(text: &str) -> String {
+    static ANSI: OnceLock<Regex> = OnceLock::new();
+    ANSI.get_or_init(|| Regex::new(r"\x1b\[[0-9;]*[A-Za-z]").unwrap())
+        .replace_all(text, "")
+        .into_owned()
+}
+
+fn classify(text: &str) -> EntryKind {
+    let first_line = text.lines().next().unwrap_or("").trim();
+
+    if first_line.is_empty()
+        || first_line.chars().all(|c| c == '-')
+        || first_line.contains("Traceback (most recent call last)")
+    {
+        return EntryKind::Header;
+    }
+
+    let is_user_frame = first_line.starts_with("Cell In[")
+        || first_line.starts_with("Input In [")
+        || first_line.contains("<ipython-input-")
+        || first_line.contains("ipykernel_");
+    if is_user_frame {
+        return EntryKind::UserFrame;
+    }
+
+    // IPython 8 prints `File path:line, in func`; older releases print
+    // `path in func(args)` for library frames.
+    let is_library_frame = first_line.starts_with("File ")
+        || (first_line.contains(" in ")
+            && first_line
+                .split_whitespace()
+                .next()
+                .is_some_and(|path| path.ends_with(".py") || path.ends_with(".pyx")));
+    if is_library_frame {
+        return EntryKind::LibraryFrame;
+    }
+
+    EntryKind::Message
+}
+
+/// Splits the traceback of an `error` output into classified entries.
+pub fn parse(output: &Output) -> Vec<TracebackEntry> {
+    output
+        .traceback_lines()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|entry| {
+            let text = strip_ansi(entry);
+            TracebackEntry {
+                kind: classify(&text),
+                text: text.trim_end().to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Summarizes an `error` output on one line as `ExceptionType: message`.
+pub fn summary(output: &Output) -> String {
+    if let Some(ename) = output.ename.as_deref().filter(|ename| !ename.is_empty()) {
+        return match output
+            .evalue
+            .as_deref()
+            .and_then(|evalue| evalue.lines().next())
+        {
+            Some(evalue) if !evalue.is_empty() => format!("{}: {}", ename, evalue),
+            _ => ename.to_string(),
+        };
+    }
+
+    parse(output)
+        .into_iter()
+        .rev()
+        .find(|entry| entry.kind == EntryKind::Message)
+        .and_then(|entry| entry.text.lines().next().map(str::to_string))
+        .unwrap_or_else(|| "error".to_string())
+}
+
+/// Renders the compact form of an `error` output's traceback.
+pub fn compact(output: &Output) -> Vec<String> {
+    compact_counted(output).0
+}
+
+/// Like `compact`, also returning the number of library frames omitted.
+pub fn compact_counted(output: &Output) -> (Vec<String>, usize) {
+    let entries = parse(output);
+    let mut lines = vec!["Traceback (most recent call last):".to_string()];
+    let mut library_run: Vec<&TracebackEntry> = Vec::new();
+    let mut omitted = 0;
+
+    for (position, entry) in entries.iter().enumerate() {
+        if entry.kind == EntryKind::LibraryFrame {
+            library_run.push(entry);
+            let run_ends_traceback = entries[position + 1..]
+                .iter()
+                .find(|next| next.kind != EntryKind::Header)
+                .is_none_or(|next| next.kind == EntryKind::Message);
+            let next_is_library = entries
+                .get(position + 1)
+                .is_some_and(|next| next.kind == EntryKind::LibraryFrame);
+            if !next_is_library {
+                omitted += flush_library_run(&mut library_run, run_ends_traceback, &mut lines);
+            }
+            continue;
+        }
+
+        match entry.kind {
+            EntryKind::Header => {}
+            EntryKind::UserFrame | EntryKind::Message => lines.push(entry.text.clone()),
+            EntryKind::LibraryFrame => unreachable!(),
+        }
+    }
+
+    // The exception type and message are always kept, even when the stored
+    // traceback is empty or ends with a frame.
+    if let Some(ename) = &output.ename {
+        let last = lines.last().map(String::as_str).unwrap_or("");
+        if !last.starts_with(ename.as_str()) {
+            match output.evalue.as_deref() {
+                Some(evalue) if !evalue.is_empty() => lines.push(format!("{}: {}", ename, evalue)),
+                _ => lines.push(ename.clone()),
+            }
+        }
+    }
+
+    (lines, omitted)
+}
+
+/// Collapses a run of consecutive library frames and returns how many were
+/// omitted. The frame that raised the exception is kept when the run ends
+/// the traceback.
+fn flush_library_run(
+    run: &mut Vec<&TracebackEntry>,
+    keep_last: bool,
+    lines: &mut Vec<String>,
+) -> usize {
+    let kept = if keep_last { run.pop() } else { None };
+    let omitted = run.len();
+    match omitted {
+        0 => {}
+        1 => lines.push("... 1 library frame omitted ...".to_string()),
+        count => lines.push(format!("... {} library frames omitted ...", count)),
+    }
+    if let Some(frame) = kept {
+        lines.push(frame.text.clone());
+    }
+    run.clear();
+    omitted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_frames() {
+        assert_eq!(classify("Cell In[12], line 3"), EntryKind::UserFrame);
+        assert_eq!(
+            classify("<ipython-input-4-9f1c> in <module>()\n----> 1 f()"),
+            EntryKind::UserFrame
+        );
+        assert_eq!(
+            classify("File ~/venv/site-packages/torch/nn/module.py:1501, in Module._call_impl"),
+            EntryKind::LibraryFrame
+        );
+        assert_eq!(
+            classify("/usr/lib/python3/site-packages/numpy/core.py in sum(a)"),
+            EntryKind::LibraryFrame
+        );
+        assert_eq!(classify("----------------------"), EntryKind::Header);
+        assert_eq!(classify("ValueError: bad shape"), EntryKind::Message);
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(
+            strip_ansi("\u{1b}[0;31mKeyError\u{1b}[0m: 'x'"),
+            "KeyError: 'x'"
+        );
+    }
+}
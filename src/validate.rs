@@ -0,0 +1,681 @@
+//! Validation against the nbformat 4.x schema rules.
+//!
+//! The validator works on the raw JSON, so it can point at the exact key that
+//! is wrong: every issue carries a JSON pointer such as
+//! `/cells/3/outputs/0/output_type` and the index of the cell it belongs to.
+//! The strict level follows the schema, including required keys, cell ids
+//! from nbformat 4.5 and unexpected properties. The lenient level only
+//! reports problems that prevent a conversion, which includes every key the
+//! notebook model requires.
+
+use crate::location::{self, ErrorLocation};
+use crate::JupyterError;
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ValidationLevel {
+    /// Follow the nbformat schema.
+    #[default]
+    Strict,
+    /// Only report problems that prevent a conversion.
+    Lenient,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    /// JSON pointer to the offending value.
+    pub pointer: String,
+    /// Zero-based index of the cell the issue belongs to.
+    pub cell_index: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pointer = if self.pointer.is_empty() {
+            "/"
+        } else {
+            &self.pointer
+        };
+        match self.cell_index {
+            Some(index) => write!(f, "{} (cell {}): {}", pointer, index + 1, self.message),
+            None => write!(f, "{}: {}", pointer, self.message),
+        }
+    }
+}
+
+const TOP_LEVEL_KEYS: &[&str] = &["metadata", "nbformat_minor", "nbformat", "cells"];
+const CODE_CELL_KEYS: &[&str] = &[
+    "id",
+    "cell_type",
+    "metadata",
+    "source",
+    "outputs",
+    "execution_count",
+];
+const TEXT_CELL_KEYS: &[&str] = &["id", "cell_type", "metadata", "source", "attachments"];
+
+/// Checks a notebook and returns `InvalidNotebookFormat` describing the first
+/// issue, if any.
+pub fn validate(content: &str, level: ValidationLevel) -> Result<(), JupyterError> {
+    let issues = Validator::new(level).validate_str(content)?;
+    let Some((first, rest)) = issues.split_first() else {
+        return Ok(());
+    };
+    let message = match rest.len() {
+        0 => first.message.clone(),
+        more => format!("{} (and {} more issue(s))", first.message, more),
+    };
+    Err(JupyterError::InvalidNotebookFormat {
+        message,
+        location: Some(Box::new(ErrorLocation::at_pointer(content, &first.pointer))),
+    })
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Validator {
+    level: ValidationLevel,
+}
+
+struct Context {
+    issues: Vec<ValidationIssue>,
+    cell_index: Option<usize>,
+}
+
+impl Context {
+    fn report(&mut self, pointer: &str, message: impl Into<String>) {
+        self.issues.push(ValidationIssue {
+            pointer: pointer.to_string(),
+            cell_index: self.cell_index,
+            message: message.into(),
+        });
+    }
+}
+
+/// Appends a reference token to a JSON pointer, escaping `~` and `/`.
+fn child(pointer: &str, token: impl fmt::Display) -> String {
+    let token = token.to_string().replace('~', "~0").replace('/', "~1");
+    format!("{}/{}", pointer, token)
+}
+
+fn is_multiline_string(value: &Value) -> bool {
+    match value {
+        Value::String(_) => true,
+        Value::Array(items) => items.iter().all(Value::is_string),
+        _ => false,
+    }
+}
+
+/// Counts in the notebook model are `u32`.
+fn is_count(value: &Value) -> bool {
+    value
+        .as_u64()
+        .is_some_and(|count| u32::try_from(count).is_ok())
+}
+
+fn is_mime_type(key: &str) -> bool {
+    key.split_once('/').is_some_and(|(kind, subtype)| {
+        !kind.is_empty()
+            && !subtype.is_empty()
+            && kind
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+    })
+}
+
+impl Validator {
+    pub fn new(level: ValidationLevel) -> Self {
+        Self { level }
+    }
+
+    fn strict(&self) -> bool {
+        self.level == ValidationLevel::Strict
+    }
+
+    /// Parses and validates a notebook. Only malformed JSON is an error;
+    /// schema violations are returned as issues.
+    pub fn validate_str(&self, content: &str) -> Result<Vec<ValidationIssue>, JupyterError> {
+        let value: Value =
+            serde_json::from_str(content).map_err(|err| location::parse_error(err, content))?;
+        Ok(self.validate_value(&value))
+    }
+
+    pub fn validate_value(&self, notebook: &Value) -> Vec<ValidationIssue> {
+        let mut context = Context {
+            issues: Vec::new(),
+            cell_index: None,
+        };
+        let Some(root) = notebook.as_object() else {
+            context.report("", "notebook must be a JSON object");
+            return context.issues;
+        };
+
+        match root.get("nbformat") {
+            Some(Value::Number(number)) if number.as_u64() == Some(4) => {}
+            Some(Value::Number(_)) if !self.strict() => {}
+            Some(Value::Number(number)) => context.report(
+                "/nbformat",
+                format!("unsupported nbformat {}, expected 4", number),
+            ),
+            Some(_) => context.report("/nbformat", "nbformat must be an integer"),
+            None => context.report("", "missing required key 'nbformat'"),
+        }
+
+        let minor = match root.get("nbformat_minor") {
+            Some(value) if is_count(value) => value.as_u64().unwrap_or_default(),
+            Some(_) => {
+                context.report(
+                    "/nbformat_minor",
+                    "nbformat_minor must be a non-negative integer",
+                );
+                0
+            }
+            None => {
+                context.report("", "missing required key 'nbformat_minor'");
+                0
+            }
+        };
+
+        match root.get("metadata") {
+            Some(Value::Object(metadata)) => {
+                self.validate_notebook_metadata(metadata, &mut context)
+            }
+            Some(_) => context.report("/metadata", "metadata must be an object"),
+            None => context.report("", "missing required key 'metadata'"),
+        }
+
+        if self.strict() {
+            for key in root.keys() {
+                if !TOP_LEVEL_KEYS.contains(&key.as_str()) {
+                    context.report(&child("", key), format!("unexpected property '{}'", key));
+                }
+            }
+        }
+
+        let Some(cells) = root.get("cells") else {
+            context.report("", "missing required key 'cells'");
+            return context.issues;
+        };
+        let Some(cells) = cells.as_array() else {
+            context.report("/cells", "cells must be an array");
+            return context.issues;
+        };
+
+        let mut ids = HashSet::new();
+        for (index, cell) in cells.iter().enumerate() {
+            context.cell_index = Some(index);
+            self.validate_cell(cell, &child("/cells", index), minor, &mut ids, &mut context);
+        }
+
+        context.issues
+    }
+
+    fn validate_notebook_metadata(&self, metadata: &Map<String, Value>, context: &mut Context) {
+        if !self.strict() {
+            return;
+        }
+        if let Some(kernelspec) = metadata.get("kernelspec") {
+            let pointer = "/metadata/kernelspec";
+            match kernelspec.as_object() {
+                Some(kernelspec) => {
+                    for key in ["name", "display_name"] {
+                        if !kernelspec.get(key).is_some_and(Value::is_string) {
+                            context
+                                .report(pointer, format!("kernelspec requires a string '{}'", key));
+                        }
+                    }
+                }
+                None => context.report(pointer, "kernelspec must be an object"),
+            }
+        }
+        if let Some(language_info) = metadata.get("language_info") {
+            let pointer = "/metadata/language_info";
+            match language_info.as_object() {
+                Some(language_info) => {
+                    if !language_info.get("name").is_some_and(Value::is_string) {
+                        context.report(pointer, "language_info requires a string 'name'");
+                    }
+                }
+                None => context.report(pointer, "language_info must be an object"),
+            }
+        }
+    }
+
+    fn validate_cell(
+        &self,
+        cell: &Value,
+        pointer: &str,
+        minor: u64,
+        ids: &mut HashSet<String>,
+        context: &mut Context,
+    ) {
+        let Some(cell) = cell.as_object() else {
+            context.report(pointer, "cell must be an object");
+            return;
+        };
+
+        let cell_type = match cell.get("cell_type") {
+            Some(Value::String(cell_type)) => cell_type.as_str(),
+            Some(_) => {
+                context.report(&child(pointer, "cell_type"), "cell_type must be a string");
+                return;
+            }
+            None => {
+                context.report(pointer, "missing required key 'cell_type'");
+                return;
+            }
+        };
+        let allowed_keys = match cell_type {
+            "code" => CODE_CELL_KEYS,
+            "markdown" | "raw" => TEXT_CELL_KEYS,
+            _ => {
+                context.report(
+                    &child(pointer, "cell_type"),
+                    format!("unknown cell type '{}'", cell_type),
+                );
+                return;
+            }
+        };
+
+        match cell.get("source") {
+            Some(source) if is_multiline_string(source) => {}
+            Some(_) => context.report(
+                &child(pointer, "source"),
+                "source must be a string or an array of strings",
+            ),
+            None => context.report(pointer, "missing required key 'source'"),
+        }
+
+        match cell.get("metadata") {
+            Some(Value::Object(_)) => {}
+            Some(_) => context.report(&child(pointer, "metadata"), "metadata must be an object"),
+            None => context.report(pointer, "missing required key 'metadata'"),
+        }
+
+        if self.strict() {
+            self.validate_cell_id(cell, pointer, minor, ids, context);
+
+            for key in cell.keys() {
+                if !allowed_keys.contains(&key.as_str()) {
+                    context.report(
+                        &child(pointer, key),
+                        format!("unexpected property '{}' in a {} cell", key, cell_type),
+                    );
+                }
+            }
+
+            if let Some(attachments) = cell.get("attachments") {
+                let attachments_pointer = child(pointer, "attachments");
+                match attachments.as_object() {
+                    Some(attachments) => {
+                        for (name, bundle) in attachments {
+                            self.validate_mime_bundle(
+                                bundle,
+                                &child(&attachments_pointer, name),
+                                context,
+                            );
+                        }
+                    }
+                    None => context.report(&attachments_pointer, "attachments must be an object"),
+                }
+            }
+        }
+
+        // Text cells must not have outputs, which strict mode reports as
+        // unexpected properties, but the model still reads them.
+        let required = self.strict() && cell_type == "code";
+
+        match cell.get("execution_count") {
+            Some(Value::Null) => {}
+            Some(count) if is_count(count) => {}
+            Some(_) => context.report(
+                &child(pointer, "execution_count"),
+                "execution_count must be a non-negative integer or null",
+            ),
+            None if required => context.report(pointer, "missing required key 'execution_count'"),
+            None => {}
+        }
+
+        match cell.get("outputs") {
+            Some(Value::Array(outputs)) => {
+                let outputs_pointer = child(pointer, "outputs");
+                for (index, output) in outputs.iter().enumerate() {
+                    self.validate_output(output, &child(&outputs_pointer, index), context);
+                }
+            }
+            Some(_) => context.report(&child(pointer, "outputs"), "outputs must be an array"),
+            None if required => context.report(pointer, "missing required key 'outputs'"),
+            None => {}
+        }
+    }
+
+    /// Cell ids are required from nbformat 4.5 on and not allowed before.
+    fn validate_cell_id(
+        &self,
+        cell: &Map<String, Value>,
+        pointer: &str,
+        minor: u64,
+        ids: &mut HashSet<String>,
+        context: &mut Context,
+    ) {
+        let id_pointer = child(pointer, "id");
+        match (cell.get("id"), minor >= 5) {
+            (None, true) => context.report(pointer, "missing required key 'id' (nbformat 4.5+)"),
+            (None, false) => {}
+            (Some(_), false) => context.report(
+                &id_pointer,
+                format!(
+                    "cell ids are only allowed from nbformat 4.5, found 4.{}",
+                    minor
+                ),
+            ),
+            (Some(Value::String(id)), true) => {
+                let well_formed = (1..=64).contains(&id.len())
+                    && id
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+                if !well_formed {
+                    context.report(
+                        &id_pointer,
+                        format!("cell id '{}' must be 1-64 letters, digits, '-' or '_'", id),
+                    );
+                } else if !ids.insert(id.clone()) {
+                    context.report(&id_pointer, format!("duplicate cell id '{}'", id));
+                }
+            }
+            (Some(_), true) => context.report(&id_pointer, "cell id must be a string"),
+        }
+    }
+
+    fn validate_output(&self, output: &Value, pointer: &str, context: &mut Context) {
+        let Some(output) = output.as_object() else {
+            context.report(pointer, "output must be an object");
+            return;
+        };
+        let output_type = match output.get("output_type") {
+            Some(Value::String(output_type)) => output_type.as_str(),
+            Some(_) => {
+                context.report(
+                    &child(pointer, "output_type"),
+                    "output_type must be a string",
+                );
+                return;
+            }
+            None => {
+                context.report(pointer, "missing required key 'output_type'");
+                return;
+            }
+        };
+
+        let required: &[&str] = match output_type {
+            "execute_result" => &["execution_count", "data", "metadata"],
+            "display_data" => &["data", "metadata"],
+            "stream" => &["name", "text"],
+            "error" => &["ename", "evalue", "traceback"],
+            _ => {
+                if self.strict() {
+                    context.report(
+                        &child(pointer, "output_type"),
+                        format!(
+                            "unknown output type '{}', expected execute_result, display_data, stream or error",
+                            output_type
+                        ),
+                    );
+                }
+                return;
+            }
+        };
+        if self.strict() {
+            for key in required {
+                if !output.contains_key(*key) {
+                    context.report(
+                        pointer,
+                        format!("missing required key '{}' in a {} output", key, output_type),
+                    );
+                }
+            }
+        }
+
+        if let Some(data) = output.get("data") {
+            self.validate_mime_bundle(data, &child(pointer, "data"), context);
+        }
+        if let Some(text) = output.get("text") {
+            if !is_multiline_string(text) {
+                context.report(
+                    &child(pointer, "text"),
+                    "text must be a string or an array of strings",
+                );
+            }
+        }
+        if let Some(traceback) = output.get("traceback") {
+            let is_string_array = traceback
+                .as_array()
+                .is_some_and(|lines| lines.iter().all(Value::is_string));
+            if !is_string_array {
+                context.report(
+                    &child(pointer, "traceback"),
+                    "traceback must be an array of strings",
+                );
+            }
+        }
+
+        for key in ["ename", "evalue"] {
+            if output.get(key).is_some_and(|value| !value.is_string()) {
+                context.report(&child(pointer, key), format!("{} must be a string", key));
+            }
+        }
+        if let Some(count) = output.get("execution_count") {
+            if !count.is_null() && !is_count(count) {
+                context.report(
+                    &child(pointer, "execution_count"),
+                    "execution_count must be a non-negative integer or null",
+                );
+            }
+        }
+
+        if !self.strict() {
+            return;
+        }
+        if let Some(name) = output.get("name") {
+            if !matches!(name.as_str(), Some("stdout") | Some("stderr")) {
+                context.report(
+                    &child(pointer, "name"),
+                    "stream name must be 'stdout' or 'stderr'",
+                );
+            }
+        }
+        if let Some(metadata) = output.get("metadata") {
+            if !metadata.is_object() {
+                context.report(&child(pointer, "metadata"), "metadata must be an object");
+            }
+        }
+    }
+
+    /// A mime bundle maps mime types to multiline strings; JSON types may hold
+    /// any JSON value.
+    fn validate_mime_bundle(&self, bundle: &Value, pointer: &str, context: &mut Context) {
+        let Some(bundle) = bundle.as_object() else {
+            context.report(pointer, "mime bundle must be an object");
+            return;
+        };
+        if !self.strict() {
+            return;
+        }
+        for (mime_type, value) in bundle {
+            let value_pointer = child(pointer, mime_type);
+            if !is_mime_type(mime_type) {
+                context.report(
+                    &value_pointer,
+                    format!("'{}' is not a mime type", mime_type),
+                );
+                continue;
+            }
+            let is_json = mime_type == "application/json" || mime_type.ends_with("+json");
+            if !is_json && !is_multiline_string(value) {
+                context.report(
+                    &value_pointer,
+                    format!("{} data must be a string or an array of strings", mime_type),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_pointer_escaping() {
+        assert_eq!(
+            child("/cells/0/outputs/1/data", "text/plain"),
+            "/cells/0/outputs/1/data/text~1plain"
+        );
+        assert_eq!(child("", "a~b"), "/a~0b");
+    }
+
+    #[test]
+    fn test_cell_ids_by_minor_version() {
+        let notebook = |minor: u64, ids: [&str; 2]| {
+            json!({
+                "metadata": {},
+                "nbformat": 4,
+                "nbformat_minor": minor,
+                "cells": ids.iter().map(|id| json!({
+                    "id": id, "cell_type": "markdown", "metadata": {}, "source": "text"
+                })).collect::<Vec<_>>(),
+            })
+        };
+        let validator = Validator::new(ValidationLevel::Strict);
+
+        assert!(validator
+            .validate_value(&notebook(5, ["a1", "b2"]))
+            .is_empty());
+
+        let issues = validator.validate_value(&notebook(5, ["a1", "a1"]));
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].pointer, "/cells/1/id");
+        assert_eq!(issues[0].cell_index, Some(1));
+
+        let issues = validator.validate_value(&notebook(4, ["a1", "b2"]));
+        assert_eq!(issues.len(), 2);
+        assert!(Validator::new(ValidationLevel::Lenient)
+            .validate_value(&notebook(4, ["a1", "b2"]))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_lenient_valid_notebooks_parse() {
+        let valid = json!({
+            "metadata": {},
+            "nbformat": 4,
+            "nbformat_minor": 4,
+            "cells": [
+                {"cell_type": "markdown", "metadata": {}, "source": "# Title\ntext"},
+                {"cell_type": "code", "metadata": {}, "source": ["x = 1"],
+                 "execution_count": 1, "outputs": [
+                    {"output_type": "stream", "name": "stdout", "text": "1\n"},
+                    {"output_type": "error", "ename": "E", "evalue": "", "traceback": []}
+                ]}
+            ],
+        });
+        let variants: [(&str, Value); 9] = [
+            ("/metadata", Value::Null),
+            ("/nbformat_minor", Value::Null),
+            ("/cells/0/metadata", Value::Null),
+            ("/cells/1/metadata", json!([])),
+            ("/cells/0/outputs", json!(5)),
+            ("/cells/1/execution_count", json!(-1)),
+            ("/cells/1/outputs/0/execution_count", json!("1")),
+            ("/cells/1/outputs/1/ename", json!(5)),
+            ("/cells/1/outputs/0/text", json!([1])),
+        ];
+
+        let validator = Validator::new(ValidationLevel::Lenient);
+        let parses = |notebook: &Value| notebook.to_string().parse::<crate::Notebook>().is_ok();
+        assert!(validator.validate_value(&valid).is_empty());
+        assert!(parses(&valid));
+        for (pointer, replacement) in variants {
+            let mut notebook = valid.clone();
+            let (parent, key) = pointer.rsplit_once('/').unwrap();
+            let object = notebook
+                .pointer_mut(parent)
+                .unwrap()
+                .as_object_mut()
+                .unwrap();
+            match replacement {
+                Value::Null => object.remove(key),
+                value => object.insert(key.to_string(), value),
+            };
+            assert!(!parses(&notebook), "{} should not parse", pointer);
+            assert!(
+                !validator.validate_value(&notebook).is_empty(),
+                "{} is not reported",
+                pointer
+            );
+        }
+    }
+
+    const MALFORMED: &str = r##"{
+ "cells": [
+  {"id": "intro", "cell_type": "markdown", "metadata": {}, "source": ["# Title"]},
+  {"id": "load", "cell_type": "code", "execution_count": 1, "metadata": {}, "source": "x = 1", "outputs": [
+   {"output_type": "display_data", "metadata": {}, "data": {"text/plain": 42}},
+   {"output_type": "widget", "data": {}}
+  ]},
+  {"id": "intro", "cell_type": "code", "metadata": {}, "source": ["y = 2"], "outputs": []}
+ ],
+ "metadata": {"kernelspec": {"name": "python3"}},
+ "nbformat": 4,
+ "nbformat_minor": 5
+}"##;
+
+    const VALID: &str = r#"{
+ "cells": [{"cell_type": "code", "execution_count": 1, "metadata": {}, "outputs": [], "source": ["x = 1"]}],
+ "metadata": {}, "nbformat": 4, "nbformat_minor": 4
+}"#;
+
+    #[test]
+    fn test_validate_strict() {
+        let validator = Validator::new(ValidationLevel::Strict);
+        let issues = validator.validate_str(MALFORMED).unwrap();
+        let lines: Vec<String> = issues.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            lines,
+            [
+                "/metadata/kernelspec: kernelspec requires a string 'display_name'",
+                "/cells/1/outputs/0/data/text~1plain (cell 2): text/plain data must be a string or an array of strings",
+                "/cells/1/outputs/1/output_type (cell 2): unknown output type 'widget', expected execute_result, display_data, stream or error",
+                "/cells/2/id (cell 3): duplicate cell id 'intro'",
+                "/cells/2 (cell 3): missing required key 'execution_count'",
+            ]
+        );
+        assert_eq!(issues[1].cell_index, Some(1));
+
+        assert!(validator.validate_str(VALID).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_validate_lenient() {
+        let validator = Validator::new(ValidationLevel::Lenient);
+        assert!(validator.validate_str(MALFORMED).unwrap().is_empty());
+        let older = VALID.replace("\"nbformat\": 4", "\"nbformat\": 3");
+        assert!(validator.validate_str(&older).unwrap().is_empty());
+        let issues = Validator::new(ValidationLevel::Strict)
+            .validate_str(&older)
+            .unwrap();
+        assert_eq!(issues[0].message, "unsupported nbformat 3, expected 4");
+
+        let unconvertible = MALFORMED.replace("\"source\": \"x = 1\"", "\"source\": 1");
+        let error = validate(&unconvertible, ValidationLevel::Lenient).unwrap_err();
+        assert!(matches!(error, JupyterError::InvalidNotebookFormat { .. }));
+        let location = error.location().unwrap();
+        assert_eq!(location.pointer.as_deref(), Some("/cells/1/source"));
+        assert_eq!(location.cell_index, Some(1));
+        assert_eq!(location.line, Some(4));
+        assert!(validate(VALID, ValidationLevel::Lenient).is_ok());
+    }
+}
@@ -0,0 +1,127 @@
+//! Read-only views of cells and outputs.
+//!
+//! The cell and output renderers are written against these traits rather
+//! than the owned model, so that the borrowed model of the `borrowed` module
+//! renders through exactly the same code.
+
+use crate::{Cell, Output};
+use std::borrow::Cow;
+
+pub trait CellView {
+    type Output: OutputView;
+
+    fn cell_type(&self) -> &str;
+
+    /// The source entries as stored, usually ending with `\n`.
+    fn source(&self) -> impl Iterator<Item = &str>;
+
+    fn execution_count(&self) -> Option<u32>;
+
+    fn outputs(&self) -> &[Self::Output];
+
+    /// Returns true if any output of the cell is an `error` output.
+    fn has_error(&self) -> bool {
+        self.outputs()
+            .iter()
+            .any(|output| output.output_type() == "error")
+    }
+}
+
+pub trait OutputView {
+    fn output_type(&self) -> &str;
+
+    /// The text of a `stream` output.
+    fn text(&self) -> Option<impl Iterator<Item = &str>>;
+
+    /// The MIME types of the data bundle in sorted order, or `None` if the
+    /// output has no data.
+    fn mime_types(&self) -> Option<Vec<&str>>;
+
+    /// The `text/plain` form of the data. A single string is one entry;
+    /// `None` if there is no `text/plain` string or list of strings.
+    fn plain_text(&self) -> Option<Vec<Cow<'_, str>>>;
+
+    /// The traceback of an `error` output, also when nested under `data`.
+    fn traceback_lines(&self) -> Option<Vec<Cow<'_, str>>>;
+
+    /// Returns true if the traceback is only found under `data`.
+    fn has_nested_traceback(&self) -> bool;
+
+    /// The output in the owned model, for the analyses that need it.
+    fn as_output(&self) -> Cow<'_, Output>;
+}
+
+impl CellView for Cell {
+    type Output = Output;
+
+    fn cell_type(&self) -> &str {
+        &self.cell_type
+    }
+
+    fn source(&self) -> impl Iterator<Item = &str> {
+        self.source.iter().map(String::as_str)
+    }
+
+    fn execution_count(&self) -> Option<u32> {
+        self.execution_count
+    }
+
+    fn outputs(&self) -> &[Output] {
+        self.outputs.as_deref().unwrap_or_default()
+    }
+}
+
+impl OutputView for Output {
+    fn output_type(&self) -> &str {
+        &self.output_type
+    }
+
+    fn text(&self) -> Option<impl Iterator<Item = &str>> {
+        Some(self.text.as_ref()?.iter().map(String::as_str))
+    }
+
+    fn mime_types(&self) -> Option<Vec<&str>> {
+        let mut mime_types: Vec<&str> = self.data.as_ref()?.keys().map(String::as_str).collect();
+        mime_types.sort_unstable();
+        Some(mime_types)
+    }
+
+    fn plain_text(&self) -> Option<Vec<Cow<'_, str>>> {
+        plain_text(self.data.as_ref()?.get("text/plain")?)
+    }
+
+    fn traceback_lines(&self) -> Option<Vec<Cow<'_, str>>> {
+        Some(
+            Output::traceback_lines(self)?
+                .into_iter()
+                .map(Cow::Borrowed)
+                .collect(),
+        )
+    }
+
+    fn has_nested_traceback(&self) -> bool {
+        self.traceback.is_none()
+            && self
+                .data
+                .as_ref()
+                .is_some_and(|data| data.contains_key("traceback"))
+    }
+
+    fn as_output(&self) -> Cow<'_, Output> {
+        Cow::Borrowed(self)
+    }
+}
+
+/// Reads a `text/plain` value, which is a string or a list of strings.
+pub(crate) fn plain_text(value: &serde_json::Value) -> Option<Vec<Cow<'_, str>>> {
+    if let Some(text) = value.as_str() {
+        Some(vec![Cow::Borrowed(text)])
+    } else {
+        value.as_array().map(|items| {
+            items
+                .iter()
+                .filter_map(|item| item.as_str().map(Cow::Borrowed))
+                .collect()
+        })
+    }
+}